@@ -0,0 +1,120 @@
+//! Runs a finding through the Lambda's handler locally, without deploying anything. Takes an SNS
+//! or EventBridge event from a file, or stdin when there's no path, and logs the payload that
+//! would be sent. With `--post` it really is sent, using the same environment as the Lambda.
+//!
+//!     cargo run --bin finding -- examples/dns_request.json
+//!     cargo run --bin finding -- --post < examples/eventbridge_dns_request.json
+//!
+//! `test` makes up a finding instead, to check the webhook, colours and mentions after a deploy
+//! without waiting for a real one. `--severity` and `--type` pick what it looks like.
+//!
+//!     cargo run --bin finding -- test --severity 9.5 --type Backdoor:EC2/C&CActivity.B --post
+
+use chrono::{SecondsFormat, Utc};
+use guarddutyslack::{handle, logging, Config};
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_runtime::Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let post = args.iter().any(|a| a == "--post");
+    let test = args.first().is_some_and(|a| a == "test");
+    let path = args.iter().find(|a| !a.starts_with("--"));
+
+    if !post {
+        // A webhook is required config, but nothing gets sent to it
+        env::set_var("DRY_RUN", "true");
+        if env::var("WEBHOOK_URL").is_err() && env::var("WEBHOOK_SECRET_ARN").is_err() {
+            env::set_var("WEBHOOK_URL", "https://hooks.slack.com/services/not/posted");
+        }
+    }
+
+    logging::init();
+
+    let event = if test {
+        let severity = match flag(&args, "--severity") {
+            Some(severity) => severity.parse()?,
+            None => 8.0,
+        };
+        let finding_type = flag(&args, "--type").unwrap_or("Recon:EC2/PortProbeUnprotectedPort");
+        test_finding(finding_type, severity)
+    } else {
+        let raw = match path {
+            Some(path) => fs::read_to_string(path)?,
+            None => {
+                let mut raw = String::new();
+                io::stdin().read_to_string(&mut raw)?;
+                raw
+            }
+        };
+        serde_json::from_str(&raw)?
+    };
+
+    let config = Config::from_env().await?;
+    let response = handle(event, &config).await?;
+    println!("{}", response);
+
+    Ok(())
+}
+
+/// The value following `name`, e.g. "8" from `--severity 8`
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1).map(String::as_str)
+}
+
+/// A made up finding of `finding_type` at `severity`, as EventBridge would deliver it. It's marked
+/// as a sample like the ones GuardDuty generates, so it's never mistaken for the real thing or
+/// pages anyone, and has a new id each time so dedupe doesn't skip it.
+fn test_finding(finding_type: &str, severity: f32) -> Value {
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let id = format!("{:032x}", rand::random::<u128>());
+    let account = "123456789012";
+    let region = env::var("AWS_REGION").unwrap_or_else(|_| String::from("us-east-1"));
+    let detector = "00000000000000000000000000000000";
+
+    json!({
+        "version": "0",
+        "id": id,
+        "detail-type": "GuardDuty Finding",
+        "source": "aws.guardduty",
+        "account": account,
+        "time": now,
+        "region": region,
+        "resources": [],
+        "detail": {
+            "schemaVersion": "2.0",
+            "accountId": account,
+            "region": region,
+            "partition": "aws",
+            "id": id,
+            "arn": format!(
+                "arn:aws:guardduty:{}:{}:detector/{}/finding/{}",
+                region, account, detector, id
+            ),
+            "type": finding_type,
+            "resource": {
+                "resourceType": "Instance",
+                "instanceDetails": { "instanceId": "i-99999999" },
+            },
+            "service": {
+                "serviceName": "guardduty",
+                "detectorId": detector,
+                "resourceRole": "TARGET",
+                "additionalInfo": { "sample": true },
+                "eventFirstSeen": now,
+                "eventLastSeen": now,
+                "archived": false,
+                "count": 1,
+            },
+            "severity": severity,
+            "createdAt": now,
+            "updatedAt": now,
+            "title": format!("Test {} finding from the finding CLI", finding_type),
+            "description": "A made up finding to check the Slack integration, there's nothing to investigate.",
+        },
+    })
+}
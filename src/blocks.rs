@@ -0,0 +1,244 @@
+//! Slack Block Kit messages, for when SLACK_FORMAT=blocks. Slack recommends these over secondary
+//! attachments, but there's no colour bar, so SEVERITY_EMOJI is worth turning on with them.
+//! https://api.slack.com/reference/block-kit/blocks
+
+use crate::fields::{display_time, TRUNCATED_NOTE};
+use crate::format::most_severe_mention;
+use crate::{Config, Message};
+use serde_json::{json, Value};
+
+/// Slack turns down a message with more blocks than this
+const MAX_BLOCKS: usize = 50;
+
+/// A section holds at most ten fields
+const MAX_FIELDS: usize = 10;
+
+/// Header text is plain text of at most 150 characters
+const MAX_HEADER_CHARS: usize = 150;
+
+/// Slack turns down the whole message if a section's text is longer than this, which escaping
+/// can take a description past
+const MAX_SECTION_CHARS: usize = 3000;
+
+/// Or if a field's text is longer than this
+const MAX_FIELD_CHARS: usize = 2000;
+
+/// One message holding every finding given, split by dividers. Like the attachments only the
+/// most severe finding carries the mention, and any findings past Slack's block limit are counted
+/// instead of shown.
+pub fn payload(messages: &[Message], config: &Config) -> Value {
+    let mentioned = most_severe_mention(
+        messages
+            .iter()
+            .map(|message| (message.detail.severity, !message.mention(config).is_empty())),
+    );
+    let mut blocks: Vec<Value> = vec![];
+    let mut shown = 0;
+    for (i, message) in messages.iter().enumerate() {
+        let mut finding = finding_blocks(message, config, Some(i) == mentioned);
+        if i > 0 {
+            finding.insert(0, json!({ "type": "divider" }));
+        }
+        // Leave room for the note about the rest
+        if blocks.len() + finding.len() > MAX_BLOCKS - 1 {
+            break;
+        }
+        blocks.extend(finding);
+        shown += 1;
+    }
+    if shown < messages.len() {
+        blocks.push(context(&format!(
+            "…and {} more findings, see the GuardDuty console",
+            messages.len() - shown
+        )));
+    }
+
+    // The text is only used for notifications once there are blocks
+    let text = match messages {
+        [message] => format!("GuardDuty:{}", message.detail.tipe),
+        _ => format!("{} GuardDuty findings", messages.len()),
+    };
+
+    let mut payload = json!({
+        "text": text,
+        "blocks": blocks,
+        "link_names": config.link_names,
+    });
+    let channel = messages
+        .first()
+        .and_then(|message| config.channel_for(message.level(config)));
+    if let Some(channel) = channel {
+        payload["channel"] = Value::from(channel);
+    }
+    payload
+}
+
+/// Splits `messages` into runs that each fit under the block limit, so none of them end up only
+/// counted in the note about the rest. A finding too big to share a message goes on its own.
+pub(crate) fn split<'a>(messages: &'a [Message], config: &Config) -> Vec<&'a [Message]> {
+    let mut runs = vec![];
+    let (mut start, mut used) = (0, 0);
+    for (i, message) in messages.iter().enumerate() {
+        let size = finding_blocks(message, config, false).len();
+        // Every finding after the first has a divider before it
+        if i > start && used + 1 + size > MAX_BLOCKS - 1 {
+            runs.push(&messages[start..i]);
+            start = i;
+            used = size;
+        } else if i > start {
+            used += 1 + size;
+        } else {
+            used = size;
+        }
+    }
+    if start < messages.len() {
+        runs.push(&messages[start..]);
+    }
+    runs
+}
+
+/// Header, where it came from, the description, the fields, the IDs with INCLUDE_IDS and a footer.
+/// Then the raw finding when VERBOSE is on.
+fn finding_blocks(message: &Message, config: &Config, with_mention: bool) -> Vec<Value> {
+    let mention = if with_mention {
+        message.mention(config)
+    } else {
+        String::new()
+    };
+    let title: String = message
+        .title(config)
+        .chars()
+        .take(MAX_HEADER_CHARS)
+        .collect();
+
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": title, "emoji": true },
+        }),
+        section(&format!(
+            "*{} in {} from account {}* {}\n<{}|{}>",
+            message.status_label(),
+            escape(&message.region_label(&config.region_names)),
+            escape(&message.account_label(&config.account_names)),
+            mention,
+            message.title_link(config),
+            match message.runbook(config) {
+                Some(_) => "Runbook",
+                None => "Finding type docs",
+            }
+        )),
+        section(&match message.api_caller() {
+            Some(caller) => format!("*{}*\n{}", escape(&caller), escape(&message.description())),
+            None => escape(&message.description()),
+        }),
+    ];
+
+    let mut fields: Vec<(&str, String)> = message
+        .summary_fields(config)
+        .into_iter()
+        .map(|(title, value)| (title, escape(&value)))
+        .collect();
+    fields.push((
+        "Console",
+        format!("<{}|View finding>", message.console_link()),
+    ));
+    fields.extend(
+        message
+            .resource_fields()
+            .into_iter()
+            .chain(message.resources_field())
+            .chain(message.tag_fields(&config.tag_keys))
+            .chain(message.scan_fields())
+            .chain(message.action_fields(config))
+            .chain(message.extra_fields(config))
+            .map(|(title, value)| (title, escape(&value))),
+    );
+    for chunk in fields.chunks(MAX_FIELDS) {
+        let fields = chunk
+            .iter()
+            .map(|(title, value)| {
+                let text = cap(format!("*{}*\n{}", title, value), MAX_FIELD_CHARS);
+                json!({ "type": "mrkdwn", "text": text })
+            })
+            .collect::<Vec<_>>();
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+
+    if config.include_ids {
+        let ids = message
+            .id_fields()
+            .into_iter()
+            .map(|(title, value)| format!("{}: `{}`", title, escape(&value)))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        blocks.push(context(&ids));
+    }
+
+    let mut footer = vec![];
+    if let Some(icon) = &config.footer_icon {
+        footer.push(json!({ "type": "image", "image_url": icon, "alt_text": "icon" }));
+    }
+    footer.push(json!({
+        "type": "mrkdwn",
+        "text": format!(
+            "{} | {}",
+            escape(&config.footer_text),
+            display_time(&message.detail.updated_at, config.display_tz)
+        ),
+    }));
+    blocks.push(json!({ "type": "context", "elements": footer }));
+
+    if config.verbose {
+        // Cut short inside the fences, so they're still closed
+        let raw = cap(escape(&message.raw_json()), MAX_SECTION_CHARS - 6);
+        blocks.push(section(&format!("```{}```", raw)));
+    }
+    blocks
+}
+
+fn section(text: &str) -> Value {
+    let text = cap(text.to_string(), MAX_SECTION_CHARS);
+    json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } })
+}
+
+fn context(text: &str) -> Value {
+    let text = cap(text.to_string(), MAX_SECTION_CHARS);
+    json!({ "type": "context", "elements": [{ "type": "mrkdwn", "text": text }] })
+}
+
+/// Cuts already escaped mrkdwn down to `max` characters, the note saying so included, without
+/// leaving half an escape behind
+fn cap(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+
+    let mut short: String = text
+        .chars()
+        .take(max - TRUNCATED_NOTE.chars().count() - 1)
+        .collect();
+    if let Some(amp) = short.rfind('&') {
+        if !short[amp..].contains(';') {
+            short.truncate(amp);
+        }
+    }
+    short.push('\n');
+    short.push_str(TRUNCATED_NOTE);
+    short
+}
+
+/// mrkdwn only needs these three escaping, so a description can't turn into a link or mention. The
+/// same as slack-hook3 does for attachment fields, so both formats show the same text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Message {
+    /// Builds a Block Kit message for the finding, for when SLACK_FORMAT=blocks
+    pub fn build_blocks(&self, config: &Config) -> Value {
+        payload(std::slice::from_ref(self), config)
+    }
+}
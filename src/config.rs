@@ -0,0 +1,847 @@
+//! Settings, read once from the environment (or CONFIG_JSON) when the Lambda starts, and the
+//! severity bands they tune.
+
+use crate::dedupe::Dedupe;
+use crate::pagerduty::PagerDuty;
+use crate::send::{Poster, RetryPolicy, Webhook};
+use crate::threads::SlackBot;
+use crate::{
+    settings, AppError, Message, QuietHours, RateLimit, RecentFindings, Sentry, Transitions,
+};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Settings read from the environment once when the Lambda starts, then shared by every invocation
+/// the warm container handles
+pub struct Config {
+    /// Where findings are posted when their severity band has no webhook of its own
+    pub webhook: String,
+    pub(crate) levels: Levels,
+    pub(crate) min_severity: f32,
+    pub(crate) suppress_archived: bool,
+    /// Post archived findings as green "Resolved" updates
+    pub notify_resolved: bool,
+    /// Only findings from these accounts are sent, every account's are when it's empty
+    pub account_allowlist: Vec<String>,
+    /// Finding types that are never sent, matched ignoring case
+    pub suppress_types: Vec<String>,
+    /// Send every finding in an invocation as one message
+    pub batch_mode: bool,
+    /// Most attachments in one batch message, more findings than fit go in another message
+    pub max_attachments: usize,
+    /// Findings seen more times than this get a repeat badge on the title
+    pub count_badge_threshold: usize,
+    /// Prefix the title with an emoji for the severity band
+    pub severity_emoji: bool,
+    pub footer_text: String,
+    /// Have Slack turn @names and #channels in the text into mentions and links. The severity
+    /// mentions need this
+    pub link_names: bool,
+    /// Add the raw finding JSON to each message
+    pub verbose: bool,
+    /// Lay short fields out in two columns, otherwise every field is full width
+    pub fields_short: bool,
+    /// Show the detector ID and finding ARN, for cross-referencing
+    pub include_ids: bool,
+    /// Show the city and country of the remote IP in place of just the country
+    pub show_location: bool,
+    /// Overrides the channel the webhook posts to
+    pub channel: Option<String>,
+    /// An extra mention for everything at or above a severity, whatever its band
+    pub threshold_mention: Option<ThresholdMention>,
+    /// Only these severities get their band's mention, when it's set
+    pub mention_severities: Option<SeverityRanges>,
+    /// Resource tags to show as fields when the finding has them, matched ignoring case
+    pub tag_keys: Vec<String>,
+    /// Always an http(s) URL, Slack rejects the whole attachment over a bad one
+    pub footer_icon: Option<String>,
+    /// How many times, for how long and on which responses posts to Slack are retried
+    pub retry: RetryPolicy,
+    pub(crate) display_tz: Tz,
+    /// Friendly names for account IDs, e.g. "123456789012" => "prod-payments"
+    pub account_names: HashMap<String, String>,
+    /// Webhooks by account ID, for an account whose findings go to a workspace of its own
+    pub account_webhooks: HashMap<String, String>,
+    /// Friendly names for region codes, e.g. "us-east-1" => "N. Virginia"
+    pub region_names: HashMap<String, String>,
+    /// Friendlier titles by finding type, e.g. "SSH brute force against EC2"
+    pub titles: HashMap<String, String>,
+    /// Fields added to every finding as (title, template), see [`crate::Message::fill_template`]
+    pub extra_fields: Vec<(String, String)>,
+    /// Runbook URLs by finding type, or the start of one, for the title to link to instead of the
+    /// AWS docs
+    pub runbooks: HashMap<String, String>,
+    /// Which chat tool the webhooks belong to, this decides the shape of the message
+    pub destination: Destination,
+    /// Attachments or Block Kit, for Slack
+    pub slack_format: SlackFormat,
+    pub(crate) dedupe: Option<Dedupe>,
+    /// Findings this container sent recently, to skip a redelivery without asking DynamoDB
+    pub recent: Option<RecentFindings>,
+    /// Only findings that are new or have changed severity band are posted, when it's set
+    pub transitions: Option<Transitions>,
+    /// Holds back findings of a type once too many have been posted
+    pub rate_limit: Option<RateLimit>,
+    /// When only critical findings are posted
+    pub quiet_hours: Option<QuietHours>,
+    pub(crate) bot: Option<SlackBot>,
+    pub(crate) pagerduty: Option<PagerDuty>,
+    /// Where errors are reported, if anywhere
+    pub sentry: Option<Sentry>,
+}
+
+/// What you get with no environment set, apart from there being no webhook
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            webhook: String::new(),
+            levels: Levels::default(),
+            min_severity: 0.0,
+            suppress_archived: false,
+            notify_resolved: false,
+            account_allowlist: vec![],
+            suppress_types: vec![],
+            batch_mode: false,
+            max_attachments: 20,
+            count_badge_threshold: 10,
+            severity_emoji: false,
+            footer_text: String::from("GuardyBot"),
+            link_names: true,
+            verbose: false,
+            fields_short: true,
+            include_ids: false,
+            show_location: false,
+            channel: None,
+            threshold_mention: None,
+            mention_severities: None,
+            tag_keys: vec![
+                String::from("Owner"),
+                String::from("Team"),
+                String::from("Environment"),
+            ],
+            footer_icon: Some(String::from(
+                "https://rustacean.net/assets/rustacean-flat-happy.png",
+            )),
+            retry: RetryPolicy::default(),
+            display_tz: Tz::UTC,
+            account_names: HashMap::new(),
+            account_webhooks: HashMap::new(),
+            region_names: HashMap::new(),
+            titles: HashMap::new(),
+            extra_fields: vec![],
+            runbooks: HashMap::new(),
+            destination: Destination::Slack,
+            slack_format: SlackFormat::Attachments,
+            dedupe: None,
+            recent: Some(RecentFindings::default()),
+            transitions: None,
+            rate_limit: None,
+            quiet_hours: None,
+            bot: None,
+            pagerduty: None,
+            sentry: None,
+        }
+    }
+}
+
+impl Config {
+    /// Every setting comes from its environment variable, or CONFIG_JSON if it isn't set
+    pub async fn from_env() -> Result<Config, AppError> {
+        settings::load()?;
+        let default = Config::default();
+        let destination = destination_from_env();
+
+        // A bot token is only any use for Slack, and it doesn't need a webhook
+        let bot = match destination {
+            Destination::Slack => SlackBot::from_env().await,
+            Destination::Teams | Destination::Generic => None,
+        };
+        let webhook = match webhook_from_env().await {
+            Err(AppError::MissingEnv(_)) if bot.is_some() => String::new(),
+            webhook => webhook?,
+        };
+
+        let levels = Levels::from_env();
+        let account_webhooks = env_map("ACCOUNT_WEBHOOK_MAP");
+
+        // A typo or placeholder should stop the Lambda starting, not fail every finding later
+        let mut webhooks = vec![("WEBHOOK_URL", webhook.as_str())];
+        for (var, level) in &[
+            ("WEBHOOK_URL_CRITICAL", &levels.critical),
+            ("WEBHOOK_URL_HIGH", &levels.high),
+            ("WEBHOOK_URL_MEDIUM", &levels.medium),
+            ("WEBHOOK_URL_LOW", &levels.low),
+        ] {
+            if let Some(webhook) = &level.webhook {
+                webhooks.push((var, webhook));
+            }
+        }
+        for webhook in account_webhooks.values() {
+            webhooks.push(("ACCOUNT_WEBHOOK_MAP", webhook));
+        }
+        // The bot posts everything to chat.postMessage, it would quietly ignore the routing
+        if bot.is_some() {
+            if let Some((var, _)) = webhooks.iter().find(|(var, _)| *var != "WEBHOOK_URL") {
+                return Err(AppError::InvalidWebhook {
+                    var: var.to_string(),
+                    reason: String::from(
+                        "SLACK_BOT_TOKEN posts with chat.postMessage rather than webhooks, route \
+                         by severity with SLACK_CHANNEL_* instead",
+                    ),
+                });
+            }
+        }
+        for (var, webhook) in webhooks {
+            if var == "WEBHOOK_URL" && webhook.is_empty() {
+                continue;
+            }
+            check_webhook(webhook, destination).map_err(|reason| AppError::InvalidWebhook {
+                var: String::from(var),
+                reason,
+            })?;
+        }
+
+        let pagerduty = PagerDuty::from_env(levels.thresholds.critical);
+
+        let config = Config {
+            webhook,
+            levels,
+            min_severity: env_parse("MIN_SEVERITY", default.min_severity),
+            suppress_archived: env_bool("SUPPRESS_ARCHIVED", default.suppress_archived),
+            notify_resolved: env_bool("NOTIFY_RESOLVED", default.notify_resolved),
+            account_allowlist: env_list("ACCOUNT_ALLOWLIST"),
+            suppress_types: env_list("SUPPRESS_TYPES"),
+            batch_mode: env_bool("BATCH_MODE", default.batch_mode),
+            max_attachments: env_parse("BATCH_MAX_ATTACHMENTS", default.max_attachments),
+            count_badge_threshold: env_parse(
+                "COUNT_BADGE_THRESHOLD",
+                default.count_badge_threshold,
+            ),
+            severity_emoji: env_bool("SEVERITY_EMOJI", default.severity_emoji),
+            footer_text: settings::var("FOOTER_TEXT").unwrap_or(default.footer_text),
+            link_names: env_bool("LINK_NAMES", default.link_names),
+            verbose: env_bool("VERBOSE", default.verbose),
+            fields_short: env_bool("FIELDS_SHORT", default.fields_short),
+            include_ids: env_bool("INCLUDE_IDS", default.include_ids),
+            show_location: env_bool("SHOW_LOCATION", default.show_location),
+            channel: settings::var("SLACK_CHANNEL")
+                .ok()
+                .filter(|c| !c.is_empty()),
+            threshold_mention: threshold_mention_from_env(),
+            mention_severities: mention_severities_from_env(),
+            tag_keys: match settings::var("TAG_KEYS") {
+                Ok(_) => env_list("TAG_KEYS"),
+                Err(_) => default.tag_keys,
+            },
+            footer_icon: footer_icon_from_env(default.footer_icon),
+            retry: RetryPolicy::from_env(),
+            display_tz: display_tz_from_env(),
+            account_names: env_map("ACCOUNT_NAME_MAP"),
+            account_webhooks,
+            region_names: env_map("REGION_NAME_MAP"),
+            titles: env_map("TITLE_MAP"),
+            extra_fields: extra_fields_from_env(),
+            runbooks: env_map("RUNBOOK_MAP"),
+            destination,
+            slack_format: slack_format_from_env(),
+            dedupe: Dedupe::from_env().await,
+            recent: RecentFindings::from_env(),
+            transitions: Transitions::from_env().await,
+            rate_limit: RateLimit::from_env(),
+            quiet_hours: QuietHours::from_env(),
+            bot,
+            pagerduty,
+            sentry: Sentry::from_env(),
+        };
+        settings::warn_unread();
+        Ok(config)
+    }
+
+    /// How many findings fit in one batch message under BATCH_MAX_ATTACHMENTS. VERBOSE gives each
+    /// finding a second attachment, and a finding too big for the cap still goes on its own.
+    pub fn batch_size(&self) -> usize {
+        let per_finding = if self.verbose { 2 } else { 1 };
+        (self.max_attachments / per_finding).max(1)
+    }
+
+    /// What messages are posted through, the bot when there's a token and otherwise the webhook
+    /// each finding is routed to
+    pub(crate) fn poster(&self) -> &dyn Poster {
+        match &self.bot {
+            Some(bot) => bot,
+            // Each a constant, so it lives as long as the config
+            None => match self.destination {
+                Destination::Slack => &Webhook(Destination::Slack),
+                Destination::Teams => &Webhook(Destination::Teams),
+                Destination::Generic => &Webhook(Destination::Generic),
+            },
+        }
+    }
+
+    /// The account's own webhook if it has one, then the level's, otherwise the default
+    /// WEBHOOK_URL. The account's wins as it may well be another workspace altogether.
+    pub fn webhook_for(&self, message: &Message) -> &str {
+        if let Some(webhook) = self.account_webhooks.get(&message.detail.account_id) {
+            return webhook;
+        }
+        let level = self.levels.from_severity(message.detail.severity);
+        level.webhook.as_deref().unwrap_or(&self.webhook)
+    }
+
+    /// The level's own channel if it has one, otherwise SLACK_CHANNEL
+    pub(crate) fn channel_for<'a>(&'a self, level: &'a SeverityLevel) -> Option<&'a str> {
+        level.channel.as_deref().or(self.channel.as_deref())
+    }
+}
+
+/// Uses the secret in WEBHOOK_SECRET_ARN if it's set, so the webhook doesn't have to sit in the
+/// Lambda environment in plaintext, otherwise WEBHOOK_URL. This only runs once at startup so
+/// Secrets Manager isn't called for every finding.
+async fn webhook_from_env() -> Result<String, AppError> {
+    if let Ok(arn) = settings::var("WEBHOOK_SECRET_ARN") {
+        log::debug!("Fetching webhook from {}", arn);
+        return fetch_secret(&arn).await;
+    }
+
+    settings::var("WEBHOOK_URL")
+        .map_err(|_| AppError::MissingEnv(String::from("WEBHOOK_URL or WEBHOOK_SECRET_ARN")))
+}
+
+/// Why `webhook` can't be posted to, if it can't. Slack webhooks are always on hooks.slack.com
+/// (hooks.slack-gov.com for GovSlack), Teams ones move around so only need to be https, and a
+/// generic one can be http too. The reason never includes the URL itself, the path is the
+/// webhook's secret.
+pub fn check_webhook(webhook: &str, destination: Destination) -> Result<(), String> {
+    let url = reqwest::Url::parse(webhook.trim()).map_err(|e| format!("not a URL ({})", e))?;
+    // An in-house service might well be plain http inside the VPC
+    if destination == Destination::Generic && url.scheme() == "http" {
+        return Ok(());
+    }
+    if url.scheme() != "https" {
+        return Err(format!("should be https, not {}", url.scheme()));
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    match destination {
+        Destination::Slack if host != "hooks.slack.com" && host != "hooks.slack-gov.com" => {
+            Err(format!(
+                "{} isn't a Slack webhook host, expected hooks.slack.com",
+                host
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The secret is expected to hold just the webhook URL as a plain string
+async fn fetch_secret(arn: &str) -> Result<String, AppError> {
+    let sdk_config = aws_config::load_from_env().await;
+    let client = aws_sdk_secretsmanager::Client::new(&sdk_config);
+
+    let secret_error = |reason: String| AppError::Secret {
+        arn: arn.to_string(),
+        reason,
+    };
+
+    let secret = client
+        .get_secret_value()
+        .secret_id(arn)
+        .send()
+        .await
+        .map_err(|e| secret_error(e.into_service_error().to_string()))?;
+
+    match secret.secret_string() {
+        Some(value) => Ok(value.trim().to_string()),
+        None => Err(secret_error(String::from("it has no SecretString"))),
+    }
+}
+
+/// Where findings get posted, set with DESTINATION
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    Slack,
+    Teams,
+    /// A NormalizedFinding as plain JSON, for custom integrations
+    Generic,
+}
+
+impl Destination {
+    /// What the logs and errors call it
+    pub fn name(self) -> &'static str {
+        match self {
+            Destination::Slack => "Slack",
+            Destination::Teams => "Teams",
+            Destination::Generic => "the webhook",
+        }
+    }
+}
+
+/// How Slack messages are laid out, set with SLACK_FORMAT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlackFormat {
+    /// Legacy attachments, with the colour bar
+    Attachments,
+    /// Block Kit
+    Blocks,
+}
+
+/// "attachments" or "blocks" from SLACK_FORMAT, attachments if it's unset or unknown
+fn slack_format_from_env() -> SlackFormat {
+    match settings::var("SLACK_FORMAT") {
+        Err(_) => SlackFormat::Attachments,
+        Ok(name) => match name.to_lowercase().as_str() {
+            "attachments" => SlackFormat::Attachments,
+            "blocks" => SlackFormat::Blocks,
+            _ => {
+                log::warn!(
+                    "WARN: SLACK_FORMAT={:?} is not attachments or blocks, using attachments",
+                    name
+                );
+                SlackFormat::Attachments
+            }
+        },
+    }
+}
+
+/// "slack" or "teams" from DESTINATION, Slack if it's unset or unknown
+fn destination_from_env() -> Destination {
+    match settings::var("DESTINATION") {
+        Err(_) => Destination::Slack,
+        Ok(name) => match name.to_lowercase().as_str() {
+            "slack" => Destination::Slack,
+            "teams" => Destination::Teams,
+            "generic" => Destination::Generic,
+            _ => {
+                log::warn!(
+                    "WARN: DESTINATION={:?} is not slack, teams or generic, using slack",
+                    name
+                );
+                Destination::Slack
+            }
+        },
+    }
+}
+
+/// FOOTER_ICON_URL if it's an http(s) URL, empty for no icon. Anything else is ignored with a
+/// warning, and there's no icon rather than one Slack would refuse.
+fn footer_icon_from_env(default: Option<String>) -> Option<String> {
+    match settings::var("FOOTER_ICON_URL") {
+        Err(_) => default,
+        Ok(url) if url.is_empty() => None,
+        Ok(url) if url.starts_with("https://") || url.starts_with("http://") => Some(url),
+        Ok(url) => {
+            log::warn!(
+                "WARN: FOOTER_ICON_URL={:?} is not an http(s) URL, leaving the icon off",
+                url
+            );
+            None
+        }
+    }
+}
+
+/// A mention for findings at or above `min_severity`, on top of their band's mention
+pub struct ThresholdMention {
+    pub min_severity: f32,
+    pub text: String,
+}
+
+/// MENTION_TEXT for findings at or above MENTION_THRESHOLD, only when both are set
+fn threshold_mention_from_env() -> Option<ThresholdMention> {
+    match (
+        settings::var("MENTION_THRESHOLD"),
+        settings::var("MENTION_TEXT"),
+    ) {
+        (Ok(threshold), Ok(text)) if !text.is_empty() => match threshold.parse() {
+            Ok(min_severity) => Some(ThresholdMention { min_severity, text }),
+            Err(_) => {
+                log::warn!(
+                    "WARN: MENTION_THRESHOLD={:?} is not a valid number, MENTION_TEXT won't be used",
+                    threshold
+                );
+                None
+            }
+        },
+        (Ok(_), _) | (_, Ok(_)) => {
+            log::warn!(
+                "WARN: MENTION_THRESHOLD and MENTION_TEXT need setting together, ignoring them"
+            );
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Severities given as single values and inclusive ranges, e.g. "7.0-10.0,5.5"
+#[derive(Debug, PartialEq)]
+pub struct SeverityRanges(Vec<RangeInclusive<f32>>);
+
+impl SeverityRanges {
+    /// A comma separated list of values like "5.5" and ranges like "7.0-10.0"
+    pub fn parse(value: &str) -> Result<SeverityRanges, String> {
+        let mut ranges = vec![];
+        for item in value.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let number = |n: &str| {
+                n.trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("{:?} is not a severity or a range of them", item))
+            };
+            let range = match item.split_once('-') {
+                Some((low, high)) => number(low)?..=number(high)?,
+                None => number(item)?..=number(item)?,
+            };
+            if range.is_empty() {
+                return Err(format!("{:?} runs backwards", item));
+            }
+            ranges.push(range);
+        }
+        if ranges.is_empty() {
+            return Err(String::from("no severities given"));
+        }
+        Ok(SeverityRanges(ranges))
+    }
+
+    pub fn contains(&self, severity: f32) -> bool {
+        self.0.iter().any(|range| range.contains(&severity))
+    }
+}
+
+/// MENTION_SEVERITIES, ignored with a warning if it doesn't parse so band mentions carry on
+fn mention_severities_from_env() -> Option<SeverityRanges> {
+    let value = settings::var("MENTION_SEVERITIES").ok()?;
+    match SeverityRanges::parse(&value) {
+        Ok(ranges) => Some(ranges),
+        Err(reason) => {
+            log::warn!(
+                "WARN: MENTION_SEVERITIES={:?} isn't usable, {}, using the band mentions",
+                value,
+                reason
+            );
+            None
+        }
+    }
+}
+
+/// An IANA zone name like "Europe/London" from DISPLAY_TZ, UTC if it's unset or unknown
+pub(crate) fn display_tz_from_env() -> Tz {
+    match settings::var("DISPLAY_TZ") {
+        Err(_) => Tz::UTC,
+        Ok(name) => name.parse().unwrap_or_else(|_| {
+            log::warn!(
+                "WARN: DISPLAY_TZ={:?} is not a known time zone, using UTC",
+                name
+            );
+            Tz::UTC
+        }),
+    }
+}
+
+/// EXTRA_FIELDS as a JSON object of field titles to templates, in title order so the fields don't
+/// move around between messages
+fn extra_fields_from_env() -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = env_map("EXTRA_FIELDS").into_iter().collect();
+    fields.sort();
+    fields
+}
+
+/// Reads a JSON object of strings like `{"123456789012":"prod-payments"}` from an environment
+/// variable. Unset or malformed JSON gives an empty map
+pub(crate) fn env_map(key: &str) -> HashMap<String, String> {
+    match settings::var(key) {
+        Err(_) => HashMap::new(),
+        Ok(value) => serde_json::from_str(&value).unwrap_or_else(|e| {
+            log::error!(
+                "ERR: {} isn't a JSON object of strings, ignoring it: {}",
+                key,
+                e
+            );
+            HashMap::new()
+        }),
+    }
+}
+
+/// Reads a comma separated list from an environment variable, unset gives an empty list
+pub(crate) fn env_list(key: &str) -> Vec<String> {
+    match settings::var(key) {
+        Err(_) => vec![],
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect(),
+    }
+}
+
+/// Reads a flag from an environment variable, "true" or "1" turn it on and anything else turns it
+/// off. Unset gives the default
+pub(crate) fn env_bool(key: &str, default: bool) -> bool {
+    match settings::var(key) {
+        Err(_) => default,
+        Ok(value) => value == "1" || value.eq_ignore_ascii_case("true"),
+    }
+}
+
+/// Reads a number from an environment variable, falls back to the default if it's unset or
+/// doesn't parse
+pub(crate) fn env_parse<T: FromStr + Display>(key: &str, default: T) -> T {
+    match settings::var(key) {
+        Err(_) => default,
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            log::warn!(
+                "WARN: {}={:?} is not a valid number, using the default of {}",
+                key,
+                value,
+                default
+            );
+            default
+        }),
+    }
+}
+
+pub(crate) struct SeverityLevel {
+    pub(crate) name: String,
+    pub(crate) colour: String,
+    pub(crate) mention: String,
+    pub(crate) emoji: String,
+    pub(crate) webhook: Option<String>,
+    pub(crate) channel: Option<String>,
+}
+
+impl SeverityLevel {
+    fn new(name: &str, colour: &str, mention: &str, emoji: &str) -> SeverityLevel {
+        SeverityLevel {
+            name: name.to_string(),
+            colour: colour.to_string(),
+            mention: mention.to_string(),
+            emoji: emoji.to_string(),
+            webhook: None,
+            channel: None,
+        }
+    }
+
+    /// Swaps the title emoji for the value of `key` when it's set
+    fn emoji_from_env(self, key: &str) -> SeverityLevel {
+        match settings::var(key) {
+            Ok(emoji) => SeverityLevel { emoji, ..self },
+            Err(_) => self,
+        }
+    }
+
+    /// Swaps the mention for the value of `key` when it's set, an empty value means no mention
+    fn mention_from_env(self, key: &str) -> SeverityLevel {
+        match settings::var(key) {
+            Ok(mention) => SeverityLevel { mention, ..self },
+            Err(_) => self,
+        }
+    }
+
+    /// Swaps the colour for the value of `key` when it's a valid hex colour, otherwise the default
+    /// is kept so a typo doesn't stop messages going out
+    fn colour_from_env(self, key: &str) -> SeverityLevel {
+        match settings::var(key) {
+            Ok(value) => match hex_colour(&value) {
+                Some(colour) => SeverityLevel { colour, ..self },
+                None => {
+                    log::warn!(
+                        "WARN: {}={:?} is not a hex colour like #FF0000, using {}",
+                        key,
+                        value,
+                        self.colour
+                    );
+                    self
+                }
+            },
+            Err(_) => self,
+        }
+    }
+
+    /// Posts this level to the channel in `key` instead of the webhook's own, when it's set
+    fn channel_from_env(self, key: &str) -> SeverityLevel {
+        match settings::var(key) {
+            Ok(channel) if !channel.is_empty() => SeverityLevel {
+                channel: Some(channel),
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// Routes this level to the webhook in `key` instead of the default, when it's set
+    fn webhook_from_env(self, key: &str) -> SeverityLevel {
+        match settings::var(key) {
+            Ok(webhook) if !webhook.is_empty() => SeverityLevel {
+                webhook: Some(webhook),
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Normalises a colour like "#ff0000" or "ff0000" to "#FF0000", None if it isn't six hex digits
+pub fn hex_colour(value: &str) -> Option<String> {
+    let digits = value.trim().trim_start_matches('#');
+    if digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(format!("#{}", digits.to_uppercase()))
+    } else {
+        None
+    }
+}
+
+/// The severities GuardDuty documents using
+/// https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_findings.html#guardduty_findings-severity
+const SEVERITY_RANGE: RangeInclusive<f32> = 1.0..=10.0;
+
+/// The lowest severity that falls in each band, anything under `low` is unknown.
+/// Defaults follow https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_findings.html#guardduty_findings-severity
+pub(crate) struct Thresholds {
+    pub(crate) critical: f32,
+    pub(crate) high: f32,
+    pub(crate) medium: f32,
+    pub(crate) low: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds {
+            critical: 9.0,
+            high: 7.0,
+            medium: 4.0,
+            low: 1.0,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Each band can be moved with SEVERITY_CRITICAL_MIN, SEVERITY_HIGH_MIN, SEVERITY_MEDIUM_MIN
+    /// and SEVERITY_LOW_MIN
+    fn from_env() -> Thresholds {
+        let default = Thresholds::default();
+        Thresholds {
+            critical: threshold_from_env("SEVERITY_CRITICAL_MIN", default.critical),
+            high: threshold_from_env("SEVERITY_HIGH_MIN", default.high),
+            medium: threshold_from_env("SEVERITY_MEDIUM_MIN", default.medium),
+            low: threshold_from_env("SEVERITY_LOW_MIN", default.low),
+        }
+    }
+}
+
+/// A threshold outside GuardDuty's severities would leave its band empty, so it gets the default
+fn threshold_from_env(key: &str, default: f32) -> f32 {
+    let threshold = env_parse(key, default);
+    if SEVERITY_RANGE.contains(&threshold) {
+        return threshold;
+    }
+
+    log::warn!(
+        "WARN: {}={} is outside GuardDuty's 1.0 to 10.0 severities, using the default of {}",
+        key,
+        threshold,
+        default
+    );
+    default
+}
+
+pub(crate) struct Levels {
+    pub(crate) thresholds: Thresholds,
+    pub(crate) critical: SeverityLevel,
+    pub(crate) high: SeverityLevel,
+    pub(crate) medium: SeverityLevel,
+    pub(crate) low: SeverityLevel,
+    pub(crate) unknown: SeverityLevel,
+    pub(crate) archived: SeverityLevel,
+    pub(crate) resolved: SeverityLevel,
+}
+
+impl Default for Levels {
+    fn default() -> Levels {
+        Levels {
+            thresholds: Thresholds::default(),
+            critical: SeverityLevel::new("Critical", Colour::RED, "@channel", "🔴"),
+            high: SeverityLevel::new("High", Colour::ORANGE, "@channel", "🟠"),
+            medium: SeverityLevel::new("Medium", Colour::YELLOW, "@here", "🟡"),
+            low: SeverityLevel::new("Low", Colour::BLUE, "", "🔵"),
+            unknown: SeverityLevel::new("Unknown", Colour::SILVER, "", "⚪"),
+            archived: SeverityLevel::new("Archived", Colour::SILVER, "", "⚪"),
+            resolved: SeverityLevel::new("Resolved", Colour::GREEN, "", "✅"),
+        }
+    }
+}
+
+impl Levels {
+    /// Each band can be changed with its own variable, `*` being CRITICAL, HIGH, MEDIUM or LOW:
+    /// MENTION_* for the mention, COLOR_* for the colour, EMOJI_* for the title emoji,
+    /// WEBHOOK_URL_* to post to its own webhook and SLACK_CHANNEL_* to post to its own channel
+    fn from_env() -> Levels {
+        let default = Levels::default();
+        Levels {
+            thresholds: Thresholds::from_env(),
+            critical: default
+                .critical
+                .mention_from_env("MENTION_CRITICAL")
+                .colour_from_env("COLOR_CRITICAL")
+                .emoji_from_env("EMOJI_CRITICAL")
+                .webhook_from_env("WEBHOOK_URL_CRITICAL")
+                .channel_from_env("SLACK_CHANNEL_CRITICAL"),
+            high: default
+                .high
+                .mention_from_env("MENTION_HIGH")
+                .colour_from_env("COLOR_HIGH")
+                .emoji_from_env("EMOJI_HIGH")
+                .webhook_from_env("WEBHOOK_URL_HIGH")
+                .channel_from_env("SLACK_CHANNEL_HIGH"),
+            medium: default
+                .medium
+                .mention_from_env("MENTION_MEDIUM")
+                .colour_from_env("COLOR_MEDIUM")
+                .emoji_from_env("EMOJI_MEDIUM")
+                .webhook_from_env("WEBHOOK_URL_MEDIUM")
+                .channel_from_env("SLACK_CHANNEL_MEDIUM"),
+            low: default
+                .low
+                .mention_from_env("MENTION_LOW")
+                .colour_from_env("COLOR_LOW")
+                .emoji_from_env("EMOJI_LOW")
+                .webhook_from_env("WEBHOOK_URL_LOW")
+                .channel_from_env("SLACK_CHANNEL_LOW"),
+            unknown: default.unknown,
+            archived: default.archived,
+            resolved: default.resolved,
+        }
+    }
+
+    /// GuardDuty only uses severities from 1.0 to 10.0, anything outside that (including a
+    /// missing severity, which comes through as 0.0) is unknown whatever the thresholds say
+    #[allow(clippy::wrong_self_convention)]
+    pub(crate) fn from_severity(&self, severity: f32) -> &SeverityLevel {
+        if !SEVERITY_RANGE.contains(&severity) {
+            return &self.unknown;
+        }
+
+        let t = &self.thresholds;
+        match severity {
+            x if x >= t.critical => &self.critical,
+            x if x >= t.high => &self.high,
+            x if x >= t.medium => &self.medium,
+            x if x >= t.low => &self.low,
+            _ => &self.unknown,
+        }
+    }
+}
+
+#[non_exhaustive]
+enum Colour {}
+#[allow(dead_code)]
+impl Colour {
+    pub const RED: &'static str = "#DF4661";
+    pub const ORANGE: &'static str = "#DB6B30";
+    pub const YELLOW: &'static str = "#FED141";
+    pub const GREEN: &'static str = "#008C95";
+    pub const BLUE: &'static str = "#00A3E0";
+    pub const SILVER: &'static str = "#BABABA";
+    pub const PINK: &'static str = "#AF1685";
+    pub const PURPLE: &'static str = "#2E1A47";
+}
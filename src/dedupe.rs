@@ -0,0 +1,164 @@
+//! Optional dedupe of re-delivered findings, backed by a DynamoDB table named in DEDUPE_TABLE.
+//! The table needs a string partition key called `id`, and TTL enabled on `expires_at` so it
+//! cleans up after itself. A warm container also remembers what it sent recently itself, which
+//! catches SNS redelivering a message straight away without a table.
+
+use crate::{env_parse, settings, Message};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use chrono::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+const DEFAULT_CACHE_SIZE: usize = 1000;
+
+pub struct Dedupe {
+    client: Client,
+    table: String,
+    ttl_secs: i64,
+}
+
+impl Dedupe {
+    /// None when DEDUPE_TABLE isn't set, so there's no AWS client and no DynamoDB calls at all.
+    /// DEDUPE_TTL_SECS sets how long a finding is remembered, a day by default.
+    pub async fn from_env() -> Option<Dedupe> {
+        let table = settings::var("DEDUPE_TABLE").ok()?;
+        let sdk_config = aws_config::load_from_env().await;
+        Some(Dedupe {
+            client: Client::new(&sdk_config),
+            table,
+            ttl_secs: env_parse("DEDUPE_TTL_SECS", 86400),
+        })
+    }
+
+    /// Records the finding as sent, true if it already had been. The put is conditional so two
+    /// invocations racing on the same finding can't both claim it. If DynamoDB can't be reached
+    /// the finding is let through, a duplicate page beats a missed one.
+    pub async fn already_sent(&self, message: &Message) -> bool {
+        let expires_at = Utc::now().timestamp() + self.ttl_secs;
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .item("id", AttributeValue::S(key(message)))
+            .item("expires_at", AttributeValue::N(expires_at.to_string()))
+            .condition_expression("attribute_not_exists(id)")
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => false,
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_conditional_check_failed_exception() {
+                    return true;
+                }
+                log::warn!(
+                    finding_id = message.detail.id.as_str();
+                    "WARN: Couldn't check {} for duplicates, sending anyway: {}",
+                    self.table,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Forgets a finding that failed to send, so a redelivery isn't taken for a duplicate
+    pub async fn forget(&self, message: &Message) {
+        let result = self
+            .client
+            .delete_item()
+            .table_name(&self.table)
+            .key("id", AttributeValue::S(key(message)))
+            .send()
+            .await;
+
+        if let Err(e) = result {
+            log::warn!(
+                finding_id = message.detail.id.as_str();
+                "WARN: Couldn't remove {} from {}, a retry will be skipped as a duplicate: {}",
+                message.detail.id,
+                self.table,
+                e.into_service_error()
+            );
+        }
+    }
+}
+
+/// The findings most recently sent by this container, the least recently seen is dropped once
+/// it's full. Only lasts as long as the container, so this is no substitute for DEDUPE_TABLE.
+pub struct RecentFindings {
+    capacity: usize,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    order: VecDeque<String>,
+    keys: HashSet<String>,
+}
+
+impl RecentFindings {
+    pub fn new(capacity: usize) -> RecentFindings {
+        RecentFindings {
+            capacity,
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+
+    /// DEDUPE_CACHE_SIZE findings are remembered, 0 turns this off
+    pub fn from_env() -> Option<RecentFindings> {
+        match env_parse("DEDUPE_CACHE_SIZE", DEFAULT_CACHE_SIZE) {
+            0 => None,
+            capacity => Some(RecentFindings::new(capacity)),
+        }
+    }
+
+    /// Records the finding as seen, true if it already had been
+    pub fn already_seen(&self, message: &Message) -> bool {
+        let key = key(message);
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+
+        if seen.keys.contains(&key) {
+            // Still being replayed, so keep it around for longer
+            seen.order.retain(|k| *k != key);
+            seen.order.push_back(key);
+            return true;
+        }
+
+        if seen.order.len() >= self.capacity {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.keys.remove(&oldest);
+            }
+        }
+        seen.keys.insert(key.clone());
+        seen.order.push_back(key);
+        false
+    }
+
+    /// Forgets a finding that failed to send, so a retry isn't taken for a replay
+    pub fn forget(&self, message: &Message) {
+        let key = key(message);
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.keys.remove(&key) {
+            seen.order.retain(|k| *k != key);
+        }
+    }
+}
+
+impl Default for RecentFindings {
+    fn default() -> RecentFindings {
+        RecentFindings::new(DEFAULT_CACHE_SIZE)
+    }
+}
+
+/// GuardDuty reuses the id when a finding recurs, bumping `updatedAt`, so a genuine update still
+/// gets through
+fn key(message: &Message) -> String {
+    format!(
+        "{}#{}",
+        message.detail.id,
+        message.detail.updated_at.to_rfc3339()
+    )
+}
@@ -0,0 +1,55 @@
+//! CloudWatch Embedded Metric Format, a JSON log line CloudWatch turns into metrics without
+//! needing a metric filter or an API call.
+//! https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html
+
+use crate::{AppError, Config, Message};
+use chrono::prelude::*;
+use serde_json::{json, Value};
+
+const NAMESPACE: &str = "GuardDutySlack";
+
+/// Writes a count of one finding to stdout, dimensioned by severity band and finding type.
+/// This has to bypass the logger, CloudWatch only picks up lines that are pure JSON.
+pub fn emit_finding_metric(message: &Message, config: &Config) {
+    println!("{}", finding_metric(message, config, Utc::now()));
+}
+
+/// The metric line for one finding, in the band its severity puts it in even once it's archived
+pub fn finding_metric(message: &Message, config: &Config, now: DateTime<Utc>) -> Value {
+    let level = config.levels.from_severity(message.detail.severity);
+    json!({
+        "_aws": {
+            "Timestamp": now.timestamp_millis(),
+            "CloudWatchMetrics": [{
+                "Namespace": NAMESPACE,
+                "Dimensions": [["Severity", "FindingType"]],
+                "Metrics": [{ "Name": "Findings", "Unit": "Count" }],
+            }],
+        },
+        "Severity": level.name,
+        "FindingType": message.detail.tipe,
+        "Findings": 1,
+    })
+}
+
+/// Writes a count of one error to stdout, dimensioned by what kind of error it was, so schema
+/// drift can be alarmed on separately from Slack being down
+pub fn emit_error_metric(e: &AppError) {
+    println!("{}", error_metric(e, Utc::now()));
+}
+
+/// The metric line for one error
+pub fn error_metric(e: &AppError, now: DateTime<Utc>) -> Value {
+    json!({
+        "_aws": {
+            "Timestamp": now.timestamp_millis(),
+            "CloudWatchMetrics": [{
+                "Namespace": NAMESPACE,
+                "Dimensions": [["ErrorType"]],
+                "Metrics": [{ "Name": "Errors", "Unit": "Count" }],
+            }],
+        },
+        "ErrorType": e.error_type(),
+        "Errors": 1,
+    })
+}
@@ -0,0 +1,94 @@
+use serde_json::Value;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Everything that can go wrong handling a finding. These are returned from the handler so the
+/// Lambda runtime records a failed invocation with a useful message, rather than a bare panic.
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error(
+        "Failed to deserialize finding {} ({length} bytes), wrong format: {source}",
+        .id.as_deref().unwrap_or("with no id")
+    )]
+    Deserialize {
+        length: usize,
+        id: Option<String>,
+        /// The message as it arrived, for error reporting
+        raw: String,
+        source: serde_json::Error,
+    },
+
+    #[error("{0} environment variable not set")]
+    MissingEnv(String),
+
+    #[error("CONFIG_JSON isn't usable: {0}")]
+    Config(String),
+
+    #[error("{var} isn't a usable webhook: {reason}")]
+    InvalidWebhook { var: String, reason: String },
+
+    #[error("Failed to read secret {arn}: {reason}")]
+    Secret { arn: String, reason: String },
+
+    #[error("Failed to send {id} to {to}: {reason}")]
+    Send {
+        id: String,
+        to: &'static str,
+        reason: String,
+    },
+
+    #[error("Failed to confirm SNS subscription at {url}: {reason}")]
+    Subscription { url: String, reason: String },
+
+    #[error("{failed} of {total} findings failed, the first because: {first}")]
+    Failed {
+        failed: usize,
+        total: usize,
+        first: Box<AppError>,
+    },
+
+    #[error("Timed out sending {id} to {to}, no response after {after:?}")]
+    Timeout {
+        id: String,
+        to: &'static str,
+        after: Duration,
+    },
+
+    #[error("Failed to build the Slack message for {id}: {reason}")]
+    Format { id: String, reason: String },
+}
+
+impl AppError {
+    /// Wraps a failure to parse `raw` as a finding. The finding id is dug out if `raw` is at least
+    /// valid JSON, so the failure can be matched up with the finding in the console.
+    pub fn deserialize(raw: &str, source: serde_json::Error) -> AppError {
+        let id = serde_json::from_str::<Value>(raw).ok().and_then(|v| {
+            v.pointer("/detail/id")
+                .and_then(Value::as_str)
+                .map(String::from)
+        });
+
+        AppError::Deserialize {
+            length: raw.len(),
+            id,
+            raw: raw.to_string(),
+            source,
+        }
+    }
+
+    /// What kind of failure this is, as the `ErrorType` of the Errors metric and the `error_type`
+    /// of the log line. A rise in "deserialize" usually means AWS changed the finding format.
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            AppError::Deserialize { .. } => "deserialize",
+            AppError::Send { .. } | AppError::Timeout { .. } => "send",
+            AppError::MissingEnv(_)
+            | AppError::Config(_)
+            | AppError::InvalidWebhook { .. }
+            | AppError::Secret { .. } => "config",
+            AppError::Subscription { .. } => "subscription",
+            AppError::Format { .. } => "format",
+            AppError::Failed { first, .. } => first.error_type(),
+        }
+    }
+}
@@ -0,0 +1,726 @@
+//! What's shown about a finding, whichever destination it goes to: the title, the mention, the
+//! fields picked out of the finding and the links to go and look at it. Each destination lays
+//! these out in its own way.
+
+use crate::config::SeverityLevel;
+use crate::{Config, Ec2Resource, Message, SCHEMA_VERSIONS};
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Slack rejects attachment text much past 3000 characters, some runtime monitoring
+/// descriptions are longer
+pub const MAX_DESCRIPTION_CHARS: usize = 3000;
+pub(crate) const TRUNCATED_NOTE: &str = "… see console for full detail";
+
+/// `text` cut short to MAX_DESCRIPTION_CHARS, the note saying so included, if it's any longer
+pub(crate) fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_DESCRIPTION_CHARS {
+        return text.to_string();
+    }
+
+    let keep = MAX_DESCRIPTION_CHARS - TRUNCATED_NOTE.chars().count() - 1;
+    let short: String = text.chars().take(keep).collect();
+    format!("{}\n{}", short, TRUNCATED_NOTE)
+}
+
+impl Message {
+    /// The band's mention, plus the MENTION_TEXT one when the finding is severe enough. Anything
+    /// the band already mentions isn't mentioned twice. With MENTION_SEVERITIES the band's mention
+    /// is only used for the severities listed, MENTION_TEXT still goes by MENTION_THRESHOLD.
+    pub(crate) fn mention(&self, config: &Config) -> String {
+        let mut mention = match &config.mention_severities {
+            Some(listed) if !listed.contains(self.detail.severity) => String::new(),
+            _ => self.level(config).mention.clone(),
+        };
+        let extra = match &config.threshold_mention {
+            Some(t) if !self.detail.service.archived && self.detail.severity >= t.min_severity => {
+                &t.text
+            }
+            _ => return mention,
+        };
+
+        for word in extra.split_whitespace() {
+            if !mention.split_whitespace().any(|m| m == word) {
+                if !mention.is_empty() {
+                    mention.push(' ');
+                }
+                mention.push_str(word);
+            }
+        }
+        mention
+    }
+
+    /// Archived findings are already dealt with, so they're greyed out and don't ping anyone. With
+    /// NOTIFY_RESOLVED they're green instead, to show the finding's been closed.
+    pub(crate) fn level<'a>(&self, config: &'a Config) -> &'a SeverityLevel {
+        if self.detail.service.archived && config.notify_resolved {
+            &config.levels.resolved
+        } else if self.detail.service.archived {
+            &config.levels.archived
+        } else {
+            config.levels.from_severity(self.detail.severity)
+        }
+    }
+
+    /// The finding type, or its TITLE_MAP title, with a badge when it's a repeat offender so it
+    /// stands out from one-offs. SEVERITY_EMOJI puts the level's emoji in front, colour bars are
+    /// easy to miss on mobile. Sample findings say so, so nobody goes hunting for an attack that
+    /// isn't there.
+    pub(crate) fn title(&self, config: &Config) -> String {
+        let mut title = self
+            .friendly_title(config)
+            .unwrap_or(self.detail.tipe.as_str())
+            .to_string();
+        if self.detail.service.count > config.count_badge_threshold {
+            title = format!("🔁 x{} {}", self.detail.service.count, title);
+        }
+        if self.is_sample() {
+            title = format!("(SAMPLE) {}", title);
+        }
+        if self.detail.service.archived && config.notify_resolved {
+            title = format!("Resolved: {}", title);
+        }
+        if config.severity_emoji {
+            title = format!("{} {}", self.level(config).emoji, title);
+        }
+        title
+    }
+
+    /// The TITLE_MAP title for the finding type, if it has one
+    pub(crate) fn friendly_title<'a>(&self, config: &'a Config) -> Option<&'a str> {
+        config.titles.get(&self.detail.tipe).map(String::as_str)
+    }
+
+    /// The instance, for findings about an EC2 instance. None for any other resource type, or if
+    /// the instance details aren't the shape expected, so the Value is still there to fall back on.
+    pub fn ec2_resource(&self) -> Option<Ec2Resource> {
+        let resource = &self.detail.resource;
+        if resource["resourceType"] != "Instance" {
+            return None;
+        }
+
+        match Ec2Resource::deserialize(&resource["instanceDetails"]) {
+            Ok(instance) => Some(instance),
+            Err(e) => {
+                log::debug!("Couldn't read the instance in {}: {}", self.detail.id, e);
+                None
+            }
+        }
+    }
+
+    /// Whether this is the first GuardDuty has said of the finding, rather than an update to one
+    /// already sent. A new finding hasn't been updated since it was created, and one only seen the
+    /// once is new too unless it's been archived since.
+    pub fn is_new(&self) -> bool {
+        self.detail.created_at == self.detail.updated_at
+            || (self.detail.service.count <= 1 && !self.detail.service.archived)
+    }
+
+    /// "New finding" or "Updated finding", so it's clear whether it's fresh or come round again
+    pub(crate) fn status_label(&self) -> &'static str {
+        if self.is_new() {
+            "New finding"
+        } else {
+            "Updated finding"
+        }
+    }
+
+    /// Whether the finding's schemaVersion is one of SCHEMA_VERSIONS
+    pub fn schema_is_known(&self) -> bool {
+        SCHEMA_VERSIONS.contains(&self.detail.schema_version.as_str())
+    }
+
+    /// GuardDuty marks the findings it generates on request as samples in `additionalInfo`
+    pub fn is_sample(&self) -> bool {
+        self.detail.service.additional_info["sample"] == true
+    }
+
+    /// The threat intel list that matched, for findings raised by one
+    pub fn threat_list(&self) -> Option<&str> {
+        self.detail.service.additional_info["threatListName"].as_str()
+    }
+
+    /// The description, cut short if it's long enough that Slack would reject the attachment
+    pub(crate) fn description(&self) -> String {
+        truncate(&self.detail.description)
+    }
+
+    /// Who called what for an AWS_API_CALL finding, e.g. "AssumedRole Admin called
+    /// DeleteTrail on cloudtrail.amazonaws.com", which says more than the title does. The caller
+    /// is whoever's in `resource.accessKeyDetails`, and as much is said as the finding has.
+    pub(crate) fn api_caller(&self) -> Option<String> {
+        let call = self.detail.service.action.get("awsApiCallAction")?;
+        let api = call["api"].as_str()?;
+
+        let key = &self.detail.resource["accessKeyDetails"];
+        let principal = match (key["userType"].as_str(), key["userName"].as_str()) {
+            (Some(user_type), Some(name)) => format!("{} {}", user_type, name),
+            (None, Some(name)) => name.to_string(),
+            _ => key["principalId"]
+                .as_str()
+                .or_else(|| key["accessKeyId"].as_str())
+                .unwrap_or("An unknown principal")
+                .to_string(),
+        };
+
+        let mut caller = format!("{} called {}", principal, api);
+        if let Some(service) = call["serviceName"].as_str() {
+            caller.push_str(&format!(" on {}", service));
+        }
+        if let Some(error) = call["errorCode"].as_str() {
+            caller.push_str(&format!(" ({})", error));
+        }
+        Some(caller)
+    }
+
+    /// The headline numbers for the finding as (title, value) pairs, shared by every destination
+    pub(crate) fn summary_fields(&self, config: &Config) -> Vec<(&'static str, String)> {
+        let service = &self.detail.service;
+        let mut fields = vec![
+            // Always there to copy into a suppression rule, even when a friendly title hides it
+            ("Finding type", self.detail.tipe.clone()),
+            // The band by severity alone, an archived finding was still as bad as it was
+            (
+                "Severity",
+                format!(
+                    "{} ({})",
+                    self.detail.severity,
+                    config.levels.from_severity(self.detail.severity).name
+                ),
+            ),
+            // The colour alone is no use to the colour blind, or anyone reading the logs
+            ("Level", self.level(config).name.clone()),
+            (
+                "First seen",
+                display_time(&service.event_first_seen, config.display_tz),
+            ),
+            ("Count", service.count.to_string()),
+            ("Last seen", self.last_seen(config)),
+            ("Detection lag", self.detection_lag()),
+        ];
+        // Archiving a finding bumps updatedAt, so that's when it was resolved
+        if service.archived && config.notify_resolved {
+            fields.push((
+                "Resolved",
+                display_time(&self.detail.updated_at, config.display_tz),
+            ));
+        }
+        if let Some(list) = self.threat_list() {
+            fields.push(("Threat list", list.to_string()));
+        }
+        if self.suppressed > 0 {
+            fields.push((
+                "Suppressed",
+                format!("{} more of this type", self.suppressed),
+            ));
+        }
+        fields
+    }
+
+    /// How long GuardDuty took to raise the finding after first seeing the activity, e.g. "12m"
+    pub(crate) fn detection_lag(&self) -> String {
+        let lag = self.detail.created_at - self.detail.service.event_first_seen;
+        if lag < chrono::Duration::zero() {
+            // Clocks disagree, the finding can't really predate what it's about
+            return String::from("0s (clock skew)");
+        }
+
+        short_duration(lag)
+    }
+
+    /// How long the activity has gone on for, e.g. "active over 3d 4h", so a finding seen for days
+    /// doesn't read as stale. None when it was only seen the once.
+    pub fn active_span(&self) -> Option<String> {
+        let service = &self.detail.service;
+        let span = service.event_last_seen - service.event_first_seen;
+        if span <= chrono::Duration::zero() {
+            return None;
+        }
+        Some(format!("active over {}", short_duration(span)))
+    }
+
+    /// The last seen time with how long it's been active alongside it
+    pub(crate) fn last_seen(&self, config: &Config) -> String {
+        let time = display_time(&self.detail.service.event_last_seen, config.display_tz);
+        match self.active_span() {
+            Some(span) => format!("{} ({})", time, span),
+            None => time,
+        }
+    }
+
+    /// "prod-payments (123456789012)" if the account has a friendly name, otherwise just the ID
+    pub(crate) fn account_label(&self, account_names: &HashMap<String, String>) -> String {
+        match account_names.get(&self.detail.account_id) {
+            Some(name) => format!("{} ({})", name, self.detail.account_id),
+            None => self.detail.account_id.clone(),
+        }
+    }
+
+    /// "N. Virginia (us-east-1)" if the region has a friendly name, otherwise just the code
+    pub(crate) fn region_label(&self, region_names: &HashMap<String, String>) -> String {
+        match region_names.get(&self.detail.region) {
+            Some(name) => format!("{} ({})", name, self.detail.region),
+            None => self.detail.region.clone(),
+        }
+    }
+
+    /// The resource type and its main identifier in a few words, e.g. "Instance i-99999999"
+    pub(crate) fn resource_label(&self) -> String {
+        match self.resource_fields().as_slice() {
+            [(_, kind), (_, id), ..] => format!("{} {}", kind, id),
+            [(_, kind)] => kind.clone(),
+            [] => String::from("Unknown resource"),
+        }
+    }
+
+    /// Picks the resource type and whichever identifiers are worth showing out of `detail.resource`.
+    /// The shape depends on `resourceType`, anything missing is left out rather than failing.
+    pub(crate) fn resource_fields(&self) -> Vec<(&'static str, String)> {
+        let resource = &self.detail.resource;
+        let resource_type = match resource["resourceType"].as_str() {
+            Some(t) => t,
+            None => return vec![],
+        };
+
+        // JSON pointers to the identifiers for each resource type
+        let identifiers: &[(&'static str, &str)] = match resource_type {
+            "Instance" => &[("Instance", "/instanceDetails/instanceId")],
+            "AccessKey" => &[
+                ("Access key", "/accessKeyDetails/accessKeyId"),
+                ("User", "/accessKeyDetails/userName"),
+            ],
+            "S3Bucket" => &[("Bucket", "/s3BucketDetails/0/name")],
+            "S3Object" => &[
+                ("Bucket", "/s3BucketDetails/0/name"),
+                ("Object", "/s3BucketDetails/0/s3ObjectDetails/0/key"),
+            ],
+            "EKSCluster" => &[
+                ("EKS cluster", "/eksClusterDetails/name"),
+                (
+                    "Workload",
+                    "/kubernetesDetails/kubernetesWorkloadDetails/name",
+                ),
+            ],
+            "ECSCluster" => &[("ECS cluster", "/ecsClusterDetails/name")],
+            "Lambda" => &[("Function", "/lambdaDetails/functionName")],
+            "RDSDBInstance" => &[("DB instance", "/rdsDbInstanceDetails/dbInstanceIdentifier")],
+            _ => &[],
+        };
+
+        let mut fields = vec![("Resource type", resource_type.to_string())];
+        for (title, pointer) in identifiers {
+            if let Some(value) = resource.pointer(pointer).and_then(Value::as_str) {
+                fields.push((*title, value.to_string()));
+            }
+        }
+        fields
+    }
+
+    /// The ARNs EventBridge lists in the event's top level `resources`, without repeats. Anything
+    /// that isn't an array of strings is taken as none.
+    pub fn resource_arns(&self) -> Vec<&str> {
+        let mut arns: Vec<&str> = vec![];
+        for arn in self.resources.as_array().into_iter().flatten() {
+            if let Some(arn) = arn.as_str().filter(|arn| !arn.is_empty()) {
+                if !arns.contains(&arn) {
+                    arns.push(arn);
+                }
+            }
+        }
+        arns
+    }
+
+    /// A quick look at everything the event says is affected, one ARN a line. None when it
+    /// doesn't list any, which GuardDuty's own events mostly don't.
+    pub(crate) fn resources_field(&self) -> Option<(&'static str, String)> {
+        let arns = self.resource_arns();
+        if arns.is_empty() {
+            return None;
+        }
+        Some(("Resource ARNs", arns.join("\n")))
+    }
+
+    /// The TAG_KEYS tags of the resource, in that order. Every resource type keeps its tags as
+    /// `[{"key": .., "value": ..}]` in its details, e.g. `instanceDetails.tags`, so any details
+    /// with tags will do. Findings without any just have no tag fields.
+    pub(crate) fn tag_fields<'a>(&self, tag_keys: &'a [String]) -> Vec<(&'a str, String)> {
+        let details = match self.detail.resource.as_object() {
+            Some(resource) => resource.values(),
+            None => return vec![],
+        };
+        let tags: Vec<&Value> = details
+            .filter_map(|details| match details {
+                // S3 findings list every bucket involved, the first is the one it's about
+                Value::Array(list) => list.first()?.get("tags"),
+                details => details.get("tags"),
+            })
+            .filter_map(Value::as_array)
+            .flatten()
+            .collect();
+
+        let mut fields = vec![];
+        for key in tag_keys {
+            let value = tags
+                .iter()
+                .find(|tag| {
+                    tag["key"]
+                        .as_str()
+                        .is_some_and(|k| k.eq_ignore_ascii_case(key))
+                })
+                .and_then(|tag| tag["value"].as_str());
+            if let Some(value) = value {
+                fields.push((key.as_str(), value.to_string()));
+            }
+        }
+        fields
+    }
+
+    /// What a Malware Protection scan found, from `ebsVolumeScanDetails` for EBS volume scans or
+    /// `malwareScanDetails` for S3 objects. Empty for any other finding.
+    pub(crate) fn scan_fields(&self) -> Vec<(&'static str, String)> {
+        let service = &self.detail.service;
+
+        // JSON pointers to the threat and where it was found, the most severe threat comes first
+        let (scan, details): (&Value, &[(&'static str, &str)]) =
+            if !service.ebs_volume_scan_details.is_null() {
+                (
+                &service.ebs_volume_scan_details,
+                &[
+                    (
+                        "Threat",
+                        "/scanDetections/highestSeverityThreatDetails/threatName",
+                    ),
+                    (
+                        "Infected files",
+                        "/scanDetections/threatsDetectedItemCount/files",
+                    ),
+                    (
+                        "File",
+                        "/scanDetections/threatDetectedByName/threatNames/0/filePaths/0/filePath",
+                    ),
+                    ("Scan ID", "/scanId"),
+                ],
+            )
+            } else if !service.malware_scan_details.is_null() {
+                (
+                    &service.malware_scan_details,
+                    &[
+                        ("Threat", "/threats/0/name"),
+                        ("File", "/threats/0/itemPaths/0/nestedItemPath"),
+                    ],
+                )
+            } else {
+                return vec![];
+            };
+
+        let mut fields = vec![];
+        for (title, pointer) in details {
+            match scan.pointer(pointer) {
+                Some(Value::String(value)) => fields.push((*title, value.clone())),
+                Some(value @ Value::Number(_)) => fields.push((*title, value.to_string())),
+                _ => {}
+            }
+        }
+        fields
+    }
+
+    /// Where the activity came from, out of `service.action`. Port probes list every prober, the
+    /// first one stands for them all. None for actions with no remote side, like DNS requests.
+    pub fn remote_ip_details(&self) -> Option<&Value> {
+        let action = &self.detail.service.action;
+        let pointer = match action["actionType"].as_str()? {
+            "NETWORK_CONNECTION" => "/networkConnectionAction/remoteIpDetails",
+            "PORT_PROBE" => "/portProbeAction/portProbeDetails/0/remoteIpDetails",
+            "AWS_API_CALL" => "/awsApiCallAction/remoteIpDetails",
+            "KUBERNETES_API_CALL" => "/kubernetesApiCallAction/remoteIpDetails",
+            "RDS_LOGIN_ATTEMPT" => "/rdsLoginAttemptAction/remoteIpDetails",
+            _ => return None,
+        };
+        action
+            .pointer(pointer)
+            .filter(|details| details.is_object())
+    }
+
+    /// The remote IP address, v4 if GuardDuty has it and v6 otherwise
+    pub fn remote_ip(&self) -> Option<&str> {
+        let details = self.remote_ip_details()?;
+        details["ipAddressV4"]
+            .as_str()
+            .or_else(|| details["ipAddressV6"].as_str())
+    }
+
+    /// "Kensington, United Kingdom", or as much of it as GuardDuty could geolocate. None when it
+    /// couldn't place the IP at all.
+    pub fn remote_location(&self) -> Option<String> {
+        let details = self.remote_ip_details()?;
+        let place = [
+            details.pointer("/city/cityName"),
+            details.pointer("/country/countryName"),
+        ]
+        .iter()
+        .filter_map(|name| name.and_then(Value::as_str))
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+        if place.is_empty() {
+            None
+        } else {
+            Some(place)
+        }
+    }
+
+    /// What was actually done, out of `service.action`. Like the resource, the shape depends on
+    /// `actionType` and whatever's missing is left out. SHOW_LOCATION swaps the country for the
+    /// city and country, when GuardDuty knows them.
+    pub(crate) fn action_fields(&self, config: &Config) -> Vec<(&'static str, String)> {
+        let action = &self.detail.service.action;
+        let action_type = match action["actionType"].as_str() {
+            Some(t) => t,
+            None => return vec![],
+        };
+
+        // JSON pointers to the interesting parts of each action type
+        let details: &[(&'static str, &str)] = match action_type {
+            "NETWORK_CONNECTION" => &[
+                ("Direction", "/networkConnectionAction/connectionDirection"),
+                (
+                    "Remote IP",
+                    "/networkConnectionAction/remoteIpDetails/ipAddressV4",
+                ),
+                (
+                    "Remote port",
+                    "/networkConnectionAction/remotePortDetails/port",
+                ),
+                (
+                    "Local port",
+                    "/networkConnectionAction/localPortDetails/port",
+                ),
+                ("Protocol", "/networkConnectionAction/protocol"),
+                (
+                    "Country",
+                    "/networkConnectionAction/remoteIpDetails/country/countryName",
+                ),
+            ],
+            "AWS_API_CALL" => &[
+                ("API", "/awsApiCallAction/api"),
+                ("Service", "/awsApiCallAction/serviceName"),
+                ("Caller type", "/awsApiCallAction/callerType"),
+                ("Error", "/awsApiCallAction/errorCode"),
+                ("Remote IP", "/awsApiCallAction/remoteIpDetails/ipAddressV4"),
+                (
+                    "Country",
+                    "/awsApiCallAction/remoteIpDetails/country/countryName",
+                ),
+            ],
+            "DNS_REQUEST" => &[
+                ("Domain", "/dnsRequestAction/domain"),
+                ("Blocked", "/dnsRequestAction/blocked"),
+            ],
+            "PORT_PROBE" => &[
+                (
+                    "Local port",
+                    "/portProbeAction/portProbeDetails/0/localPortDetails/port",
+                ),
+                (
+                    "Remote IP",
+                    "/portProbeAction/portProbeDetails/0/remoteIpDetails/ipAddressV4",
+                ),
+                ("Blocked", "/portProbeAction/blocked"),
+            ],
+            "KUBERNETES_API_CALL" => &[
+                ("Verb", "/kubernetesApiCallAction/verb"),
+                ("Request URI", "/kubernetesApiCallAction/requestUri"),
+                (
+                    "Remote IP",
+                    "/kubernetesApiCallAction/remoteIpDetails/ipAddressV4",
+                ),
+            ],
+            "RDS_LOGIN_ATTEMPT" => &[(
+                "Remote IP",
+                "/rdsLoginAttemptAction/remoteIpDetails/ipAddressV4",
+            )],
+            _ => &[],
+        };
+
+        let mut fields = vec![("Action", action_type.to_string())];
+        for (title, pointer) in details {
+            // Ports are numbers and blocked is a bool, objects and arrays aren't worth showing
+            match action.pointer(pointer) {
+                Some(Value::String(value)) => fields.push((*title, value.clone())),
+                Some(value @ (Value::Number(_) | Value::Bool(_))) => {
+                    fields.push((*title, value.to_string()))
+                }
+                _ => {}
+            }
+        }
+
+        if config.show_location {
+            if let Some(location) = self.remote_location() {
+                fields.retain(|(title, _)| *title != "Country");
+                fields.push(("Location", location));
+            }
+        }
+        fields
+    }
+
+    /// The RUNBOOK_MAP runbook for the finding type, from an exact match or else the longest key
+    /// the type starts with, so "Recon:EC2/" covers every EC2 recon finding. Matched ignoring case.
+    pub fn runbook<'a>(&self, config: &'a Config) -> Option<&'a str> {
+        let tipe = self.detail.tipe.to_lowercase();
+        config
+            .runbooks
+            .iter()
+            .map(|(key, url)| (key.to_lowercase(), url))
+            .filter(|(key, _)| tipe.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, url)| url.as_str())
+    }
+
+    /// Where the title goes, the finding type's runbook if it has one, otherwise the AWS docs
+    pub(crate) fn title_link(&self, config: &Config) -> String {
+        match self.runbook(config) {
+            Some(runbook) => runbook.to_string(),
+            None => self.finding_link(),
+        }
+    }
+
+    /// The detector that raised the finding and the finding's full ARN, for INCLUDE_IDS
+    pub(crate) fn id_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Detector ID", self.detail.service.detector_id.clone()),
+            ("Finding ARN", self.detail.arn.clone()),
+        ]
+    }
+
+    /// The EXTRA_FIELDS, filled in for this finding
+    pub(crate) fn extra_fields<'a>(&self, config: &'a Config) -> Vec<(&'a str, String)> {
+        config
+            .extra_fields
+            .iter()
+            .map(|(title, template)| (title.as_str(), self.fill_template(template)))
+            .collect()
+    }
+
+    /// Fills `{id}`, `{account}`, `{region}` and `{type}` in with the finding's own, e.g. a link to
+    /// "https://siem.example.com/search?q={id}". Anything else in braces is left as it is.
+    pub fn fill_template(&self, template: &str) -> String {
+        template
+            .replace("{id}", &self.detail.id)
+            .replace("{account}", &self.detail.account_id)
+            .replace("{region}", &self.detail.region)
+            .replace("{type}", &self.detail.tipe)
+    }
+
+    /// Deep link to this finding in the GuardDuty console, GovCloud and China have consoles of
+    /// their own
+    pub(crate) fn console_link(&self) -> String {
+        let host = match self.detail.partition.as_str() {
+            "aws-us-gov" => String::from("console.amazonaws-us-gov.com"),
+            "aws-cn" => String::from("console.amazonaws.cn"),
+            _ => format!("{}.console.aws.amazon.com", self.detail.region),
+        };
+
+        format!(
+            "https://{host}/guardduty/home?region={region}#/findings?fId={id}",
+            host = host,
+            region = self.detail.region,
+            id = self.detail.id
+        )
+    }
+
+    /// Performs the required transformation to turn an AWS finding name string into the correct
+    /// URL to their GuardDuty docs. These aren't all deterministic (IAMUser links to iam.html) so
+    /// the group is looked up in DOC_GROUPS. Deliberately setup in a way to fail if a new finding
+    /// category comes out, so we don't start sending bad links. Those fall back to the list of all
+    /// finding types, so the title still goes somewhere useful.
+    pub fn finding_link(&self) -> String {
+        let finding = &self.detail.tipe;
+        let base_url = "https://docs.aws.amazon.com/guardduty/latest/ug/";
+        let fallback = format!("{}guardduty_finding-types-active.html", base_url);
+
+        let re = Regex::new(r":([^:/]+)/").unwrap(); // Capture "bar" from "foo:bar/baz"
+        let lower_finding = &finding.to_lowercase(); // Downcase the string
+
+        let group = match re.captures(lower_finding) {
+            Some(caps) => normalize_group(&caps[1]),
+            None => {
+                log::error!("ERR: Couldn't match a finding group in: {}", &finding);
+                return fallback;
+            }
+        };
+
+        let (slug, page) = match DOC_GROUPS.iter().find(|(name, _, _)| *name == group) {
+            Some((_, slug, page)) => (*slug, *page),
+            None => {
+                log::error!("ERR: Got unexpected finding group: {:?}", group);
+                return fallback;
+            }
+        };
+
+        // Malware findings (foo:ec2/maliciousfile) share a page whatever the group
+        let page = if lower_finding.contains("/maliciousfile") {
+            "findings-malware-protection"
+        } else {
+            page
+        };
+
+        let anchor = re.replace(lower_finding, format!("-{}-", slug));
+
+        format!("{}{}.html#{}", base_url, page, anchor)
+    }
+}
+
+/// Every finding group there are docs for: the group as it's written in the finding type once
+/// normalized, the name its anchors use, and the page its finding types are listed on. The
+/// original groups each have a page named after them, the newer protection plans have their own.
+const DOC_GROUPS: &[(&str, &str, &str)] = &[
+    ("iamuser", "iam", "guardduty_finding-types-iam"),
+    ("ec2", "ec2", "guardduty_finding-types-ec2"),
+    ("s3", "s3", "guardduty_finding-types-s3"),
+    (
+        "kubernetes",
+        "kubernetes",
+        "guardduty_finding-types-kubernetes",
+    ),
+    ("rds", "rds", "findings-rds-protection"),
+    ("lambda", "lambda", "lambda-protection-finding-types"),
+    ("runtime", "runtime", "findings-runtime-monitoring"),
+    ("ecs", "ecs", "findings-malware-protection"),
+    ("container", "container", "findings-malware-protection"),
+];
+
+/// Lowercases a finding group and drops anything that isn't a letter or digit, so "IAM-User"
+/// and "IAMUser" are the same group
+fn normalize_group(group: &str) -> String {
+    group
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// A duration in its two biggest units, e.g. "3d 4h", "2h 32m" or "16m". Seconds only show when
+/// it's under a minute.
+fn short_duration(duration: chrono::Duration) -> String {
+    let (days, hours, minutes) = (
+        duration.num_days(),
+        duration.num_hours() % 24,
+        duration.num_minutes() % 60,
+    );
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", duration.num_seconds()),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+/// e.g. "Wed Dec 22 11:27:25 GMT", in the zone set by DISPLAY_TZ
+pub(crate) fn display_time(time: &DateTime<Utc>, tz: Tz) -> String {
+    time.with_timezone(&tz).format("%a %b %e %T %Z").to_string()
+}
@@ -0,0 +1,57 @@
+//! The filters in the config a finding goes through before anything is built for it. Quiet hours,
+//! rate limits and dedupe depend on what came before, so they're left to the handler.
+
+use crate::{Config, Message};
+
+/// Why a finding was left out rather than sent
+#[derive(Debug, PartialEq)]
+pub enum Skip {
+    Archived,
+    BelowMinSeverity,
+    SuppressedType,
+    AccountNotAllowed,
+}
+
+impl Skip {
+    /// The reason given in the handler's response
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Skip::Archived => "archived",
+            Skip::BelowMinSeverity => "below_min_severity",
+            Skip::SuppressedType => "suppressed_type",
+            Skip::AccountNotAllowed => "account_not_allowed",
+        }
+    }
+}
+
+impl Message {
+    /// Checks the finding against the filters in the config, before any work goes into building a
+    /// payload for it
+    pub fn skip_reason(&self, config: &Config) -> Option<Skip> {
+        // From an account in the organisation that someone else looks after
+        if !config.account_allowlist.is_empty()
+            && !config.account_allowlist.contains(&self.detail.account_id)
+        {
+            return Some(Skip::AccountNotAllowed);
+        }
+
+        // NOTIFY_RESOLVED asks for exactly the findings SUPPRESS_ARCHIVED would skip, so it wins
+        if self.detail.service.archived && config.suppress_archived && !config.notify_resolved {
+            return Some(Skip::Archived);
+        }
+
+        if self.detail.severity < config.min_severity {
+            return Some(Skip::BelowMinSeverity);
+        }
+
+        if config
+            .suppress_types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&self.detail.tipe))
+        {
+            return Some(Skip::SuppressedType);
+        }
+
+        None
+    }
+}
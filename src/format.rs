@@ -0,0 +1,355 @@
+//! How a finding is laid out as a Slack attachment. Single findings go through a [`Formatter`],
+//! so a team can bring its own layout without forking the crate. Batches share attachments by
+//! finding type, and a plain text message stands in when slack-hook3 won't build any of it.
+
+use crate::fields::truncate;
+use crate::{AppError, Config, Message};
+use serde_json::{json, Value};
+use slack_hook3::{
+    Attachment, AttachmentBuilder, Field, Payload, PayloadBuilder, SlackLink, SlackTextContent,
+};
+use std::fmt::Display;
+
+pub trait Formatter {
+    fn format(&self, message: &Message) -> Result<Payload, AppError>;
+}
+
+/// The layout this crate has always posted, tuned by the settings in its [`Config`]
+pub struct DefaultFormatter<'a> {
+    config: &'a Config,
+}
+
+impl<'a> DefaultFormatter<'a> {
+    pub fn new(config: &'a Config) -> DefaultFormatter<'a> {
+        DefaultFormatter { config }
+    }
+}
+
+impl Formatter for DefaultFormatter<'_> {
+    fn format(&self, message: &Message) -> Result<Payload, AppError> {
+        let config = self.config;
+        let channel = config.channel_for(message.level(config));
+        let attachments = message.build_attachments(config, true)?;
+        payload(&message.detail.id, attachments, channel, config)
+    }
+}
+
+/// The Slack message for a finding with the default settings, as if no environment was set
+pub fn message_to_payload(message: &Message) -> Result<Payload, AppError> {
+    message.build_payload(&Config::default())
+}
+
+/// Puts every finding in one message, in the order given. Findings of the same type share one
+/// attachment with a count, so a burst of port probes doesn't fill the channel. Only the most
+/// severe attachment with a mention gets it, so one message never pings more than once.
+pub fn build_batch_payload(messages: &[Message], config: &Config) -> Result<Payload, AppError> {
+    let groups = group_by_type(messages);
+    // A group's attachment carries the mention of the finding it leads with
+    let mentioned = most_severe_mention(groups.iter().map(|group| {
+        let severity = group
+            .iter()
+            .map(|message| message.detail.severity)
+            .fold(f32::MIN, f32::max);
+        (severity, !group[0].mention(config).is_empty())
+    }));
+
+    let mut attachments = vec![];
+    for (i, group) in groups.iter().enumerate() {
+        match group.as_slice() {
+            [message] => {
+                attachments.extend(message.build_attachments(config, Some(i) == mentioned)?)
+            }
+            _ => attachments.push(group[0].build_group_attachment(
+                group,
+                config,
+                Some(i) == mentioned,
+            )?),
+        }
+    }
+    let channel = messages
+        .first()
+        .and_then(|message| config.channel_for(message.level(config)));
+    let ids = messages
+        .iter()
+        .map(|m| &*m.detail.id)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    payload(&ids, attachments, channel, config)
+}
+
+/// A bare text message for when slack-hook3 won't build the attachments, one line per finding
+/// linking to the console. Nothing here can fail, so the channel still hears about the findings.
+pub fn fallback_payload(messages: &[Message], config: &Config) -> Value {
+    let text = messages
+        .iter()
+        .map(|message| {
+            format!(
+                "GuardDuty finding {} of type {}, severity {} — <{}|see console>",
+                message.detail.id,
+                message.detail.tipe,
+                message.detail.severity,
+                message.console_link()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut payload = json!({ "text": text });
+    if let Some(channel) = messages
+        .first()
+        .and_then(|message| config.channel_for(message.level(config)))
+    {
+        payload["channel"] = json!(channel);
+    }
+    payload
+}
+
+/// The index of the most severe part of a batch with a mention to make, the first of them on a
+/// tie, for the one part that carries the mention. A band with an empty mention is passed over
+/// rather than leaving the batch silent.
+pub(crate) fn most_severe_mention(parts: impl IntoIterator<Item = (f32, bool)>) -> Option<usize> {
+    let mut highest: Option<(usize, f32)> = None;
+    for (i, (severity, mentions)) in parts.into_iter().enumerate() {
+        if mentions && highest.is_none_or(|(_, h)| severity > h) {
+            highest = Some((i, severity));
+        }
+    }
+    highest.map(|(i, _)| i)
+}
+
+/// Gathers findings of the same type together, in the order each type first turns up. With the
+/// findings sorted by severity, each group leads with its most severe.
+pub fn group_by_type(messages: &[Message]) -> Vec<Vec<&Message>> {
+    let mut groups: Vec<Vec<&Message>> = vec![];
+    for message in messages {
+        match groups
+            .iter_mut()
+            .find(|group| group[0].detail.tipe == message.detail.tipe)
+        {
+            Some(group) => group.push(message),
+            None => groups.push(vec![message]),
+        }
+    }
+    groups
+}
+
+/// Without a `channel` the message goes wherever the webhook was set up to post. `id` is the
+/// finding, or findings, the message is for, to say which in the error if it can't be built.
+fn payload(
+    id: &str,
+    attachments: Vec<Attachment>,
+    channel: Option<&str>,
+    config: &Config,
+) -> Result<Payload, AppError> {
+    let mut builder = PayloadBuilder::new()
+        .attachments(attachments)
+        .link_names(config.link_names);
+    if let Some(channel) = channel {
+        builder = builder.channel(channel);
+    }
+
+    builder.build().map_err(|e| AppError::Format {
+        id: id.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+impl Message {
+    /// Sets up the Slack payload, constructed loosely around the Slack BlockKit format.
+    /// Note you can't repeat things, like .text().text()
+    ///
+    /// This is built using the excellent slack-hook3 crate, this is a branch of a fork of a fork because
+    /// the original maintainer doesn't look after slack-hook any more.
+    /// https://github.com/0xc0deface/rust-slack/tree/v3
+    pub fn build_payload(&self, config: &Config) -> Result<Payload, AppError> {
+        DefaultFormatter::new(config).format(self)
+    }
+
+    /// The finding's attachment, followed by the raw finding when VERBOSE is on
+    fn build_attachments(
+        &self,
+        config: &Config,
+        with_mention: bool,
+    ) -> Result<Vec<Attachment>, AppError> {
+        let mut attachments = vec![self.build_attachment(config, with_mention)?];
+        if config.verbose {
+            attachments.push(self.build_raw_attachment()?);
+        }
+        Ok(attachments)
+    }
+
+    /// The whole finding as pretty printed JSON in a code block, for VERBOSE
+    fn build_raw_attachment(&self) -> Result<Attachment, AppError> {
+        AttachmentBuilder::new(format!("Raw finding {}", self.detail.id))
+            .text(format!("```{}```", self.raw_json()))
+            .build()
+            .map_err(|e| self.format_error(e))
+    }
+
+    /// slack-hook3 refusing to build part of this finding's message
+    fn format_error(&self, e: impl Display) -> AppError {
+        AppError::Format {
+            id: self.detail.id.clone(),
+            reason: e.to_string(),
+        }
+    }
+
+    /// The finding as pretty printed JSON, cut short for Slack like the description. This
+    /// serializes the Message itself so it matches what GuardDuty sent.
+    pub(crate) fn raw_json(&self) -> String {
+        let json = serde_json::to_string_pretty(self).unwrap_or_else(|e| e.to_string());
+        truncate(&json)
+    }
+
+    /// One finding's attachment. The mention can be left off so a batch only pings once
+    fn build_attachment(
+        &self,
+        config: &Config,
+        with_mention: bool,
+    ) -> Result<Attachment, AppError> {
+        let level = self.level(config);
+        let account = self.account_label(&config.account_names);
+
+        let fallback = format!(
+            "GuardDuty:{} in {} {}",
+            self.detail.tipe, account, self.detail.region
+        );
+
+        // Side by side in two columns unless FIELDS_SHORT is off
+        let short = Some(config.fields_short);
+        let slack_field = |(title, value): (&str, String)| Field::new(title, value, short);
+
+        // The console link goes after the summary, ahead of the resource identifiers
+        let mut fields: Vec<Field> = self
+            .summary_fields(config)
+            .into_iter()
+            .map(slack_field)
+            .collect();
+        fields.push(Field {
+            title: String::from("Console"),
+            value: [SlackTextContent::Link(SlackLink::new(
+                &self.console_link(),
+                "View finding",
+            ))][..]
+                .into(),
+            short,
+        });
+        fields.extend(self.resource_fields().into_iter().map(slack_field));
+        // ARNs are too long to sit alongside anything
+        fields.extend(
+            self.resources_field()
+                .map(|(title, value)| Field::new(title, value, Some(false))),
+        );
+        fields.extend(
+            self.tag_fields(&config.tag_keys)
+                .into_iter()
+                .map(slack_field),
+        );
+        fields.extend(self.scan_fields().into_iter().map(slack_field));
+        fields.extend(self.action_fields(config).into_iter().map(slack_field));
+        if config.include_ids {
+            // An ARN is too long to sit alongside anything
+            fields.extend(
+                self.id_fields()
+                    .into_iter()
+                    .map(|(title, value)| Field::new(title, value, Some(false))),
+            );
+        }
+        fields.extend(self.extra_fields(config).into_iter().map(slack_field));
+
+        let mention = if with_mention {
+            self.mention(config)
+        } else {
+            String::new()
+        };
+
+        let mut attachment = AttachmentBuilder::new(fallback)
+            .color(&level.colour)
+            .pretext(format!(
+                "*{} in {} from account {}* {}",
+                self.status_label(),
+                self.region_label(&config.region_names),
+                account,
+                mention
+            ))
+            .title(self.title(config))
+            .title_link(&self.title_link(config))
+            .text(match self.api_caller() {
+                Some(caller) => format!("*{}*\n{}", caller, self.description()),
+                None => self.description(),
+            })
+            .fields(fields)
+            .footer(&*config.footer_text)
+            // SlackTime sends the epoch seconds of a naive time, so it has to be the UTC one
+            .ts(&self.detail.updated_at.naive_utc());
+        if let Some(icon) = &config.footer_icon {
+            attachment = attachment.footer_icon(icon);
+        }
+
+        attachment.build().map_err(|e| self.format_error(e))
+    }
+
+    /// One attachment standing in for a `group` of findings of this type in a batch, this being
+    /// the most severe of them. The pretext has the count, and what each finding was about is
+    /// listed in place of the one description.
+    fn build_group_attachment(
+        &self,
+        group: &[&Message],
+        config: &Config,
+        with_mention: bool,
+    ) -> Result<Attachment, AppError> {
+        let level = self.level(config);
+        let summary = format!("{} {} findings", group.len(), self.detail.tipe);
+
+        let affected = group
+            .iter()
+            .map(|message| {
+                format!(
+                    "{} in {} {}",
+                    message.resource_label(),
+                    message.account_label(&config.account_names),
+                    message.detail.region
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let fields = vec![
+            Field::new(
+                "Highest severity",
+                format!(
+                    "{} ({})",
+                    self.detail.severity,
+                    config.levels.from_severity(self.detail.severity).name
+                ),
+                Some(config.fields_short),
+            ),
+            Field::new("Count", group.len().to_string(), Some(config.fields_short)),
+            Field::new("Affected resources", affected, Some(false)),
+        ];
+
+        let mention = if with_mention {
+            self.mention(config)
+        } else {
+            String::new()
+        };
+        let updated_at = group
+            .iter()
+            .map(|message| message.detail.updated_at)
+            .max()
+            .unwrap_or(self.detail.updated_at);
+
+        let mut attachment = AttachmentBuilder::new(format!("GuardDuty: {}", summary))
+            .color(&level.colour)
+            .pretext(format!("*{} in this delivery* {}", summary, mention))
+            .title(self.title(config))
+            .title_link(&self.title_link(config))
+            .fields(fields)
+            .footer(&*config.footer_text)
+            .ts(&updated_at.naive_utc());
+        if let Some(icon) = &config.footer_icon {
+            attachment = attachment.footer_icon(icon);
+        }
+
+        attachment.build().map_err(|e| self.format_error(e))
+    }
+}
@@ -0,0 +1,80 @@
+//! Plain JSON for anything that isn't a chat tool, for when DESTINATION=generic. Each finding is
+//! flattened into a NormalizedFinding, so whatever's listening gets the same fields however
+//! GuardDuty changes its own format.
+
+use crate::{Config, Message};
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// What DESTINATION=generic posts for each finding. Fields are only ever added to this, never
+/// renamed or removed, so integrations built on it keep working.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NormalizedFinding {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub tipe: String,
+    pub severity: f32,
+    /// The severity band, e.g. "High", with SEVERITY_*_MIN taken into account
+    pub severity_label: String,
+    pub account_id: String,
+    /// From ACCOUNT_NAME_MAP, if the account is in it
+    pub account_name: Option<String>,
+    pub region: String,
+    pub title: String,
+    pub description: String,
+    pub console_link: String,
+    pub count: usize,
+    pub archived: bool,
+    pub sample: bool,
+    /// How many findings of this type RATE_LIMIT_COUNT held back before this one
+    pub suppressed: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl NormalizedFinding {
+    pub fn new(message: &Message, config: &Config) -> NormalizedFinding {
+        let detail = &message.detail;
+        NormalizedFinding {
+            id: detail.id.clone(),
+            tipe: detail.tipe.clone(),
+            severity: detail.severity,
+            severity_label: message.level(config).name.clone(),
+            account_id: detail.account_id.clone(),
+            account_name: config.account_names.get(&detail.account_id).cloned(),
+            region: detail.region.clone(),
+            title: detail.title.clone(),
+            description: detail.description.clone(),
+            console_link: message.console_link(),
+            count: detail.service.count,
+            archived: detail.service.archived,
+            sample: message.is_sample(),
+            suppressed: message.suppressed,
+            created_at: detail.created_at,
+            updated_at: detail.updated_at,
+            first_seen: detail.service.event_first_seen,
+            last_seen: detail.service.event_last_seen,
+        }
+    }
+}
+
+/// A lone finding is posted as its NormalizedFinding, a batch as an array of them
+pub fn body(messages: &[Message], config: &Config) -> Value {
+    match messages {
+        [message] => json!(NormalizedFinding::new(message, config)),
+        _ => json!(messages
+            .iter()
+            .map(|m| NormalizedFinding::new(m, config))
+            .collect::<Vec<_>>()),
+    }
+}
+
+impl Message {
+    /// The plain JSON for the finding, for when DESTINATION=generic
+    pub fn build_normalized(&self, config: &Config) -> NormalizedFinding {
+        NormalizedFinding::new(self, config)
+    }
+}
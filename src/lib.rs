@@ -1,280 +1,708 @@
+//! Posts GuardDuty findings to Slack. The binary runs this as a Lambda, but the formatting can be
+//! used on its own: deserialize a finding into a [`Message`] and turn it into a Slack
+//! [`Payload`](slack_hook3::Payload) with [`message_to_payload`], or [`Message::build_payload`] to
+//! pick the settings.
+//!
+//! The finding itself is in [`model`], the settings in [`config`], the Slack attachments in
+//! [`format`] and posting them in [`send`]. What's shown about a finding and the filters it goes
+//! through have modules of their own too, the handler reading the event and putting each finding
+//! through them is here. The main types are re-exported at the top level.
+
 use aws_lambda_events::event::sns::SnsEvent;
 use chrono::prelude::*;
 use lambda_runtime::{handler_fn, Context, Error};
-use log::LevelFilter;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use simple_logger::SimpleLogger;
-use slack_hook3::{AttachmentBuilder, Field, Payload, PayloadBuilder, Slack};
-use std::env;
+
+mod blocks;
+pub mod config;
+mod dedupe;
+mod emf;
+mod error;
+mod fields;
+mod filter;
+pub mod format;
+mod generic;
+pub mod logging;
+pub mod model;
+mod pagerduty;
+mod quiet;
+mod ratelimit;
+pub mod send;
+mod sentry;
+pub mod settings;
+mod teams;
+mod threads;
+mod transitions;
+
+pub use config::{
+    check_webhook, hex_colour, Config, Destination, SeverityRanges, SlackFormat, ThresholdMention,
+};
+use config::{display_tz_from_env, env_bool, env_parse};
+pub use dedupe::RecentFindings;
+pub use emf::{error_metric as emf_error_metric, finding_metric as emf_finding_metric};
+pub use error::AppError;
+pub use fields::MAX_DESCRIPTION_CHARS;
+pub use filter::Skip;
+pub use format::{
+    build_batch_payload, fallback_payload, group_by_type, message_to_payload, DefaultFormatter,
+    Formatter,
+};
+pub use generic::NormalizedFinding;
+pub use model::{Detail, Ec2Resource, Message, NetworkInterface, Service, Tag, SCHEMA_VERSIONS};
+pub use quiet::QuietHours;
+pub use ratelimit::{HeldBack, Limit, RateLimit, Route};
+use send::send;
+pub use send::RetryPolicy;
+pub use sentry::{event as sentry_event, Sentry};
+pub use transitions::Transitions;
 
 #[tokio::main]
 pub async fn main() -> Result<(), Error> {
-    // Takes log level from RUST_LOG [off, error, warn, info, debug, trace]
-    // https://docs.rs/env_logger/latest/env_logger/#enabling-logging
-    SimpleLogger::new()
-        .env()
-        .with_level(LevelFilter::Info)
-        .without_timestamps()
-        .init()
-        .unwrap();
+    logging::init();
 
-    let handler = handler_fn(handler);
+    // A bad setting fails every invocation, it gets counted once here rather than each time
+    let config = match Config::from_env().await {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!(error_type = e.error_type(); "ERR: {}", e);
+            emf::emit_error_metric(&e);
+            return Err(e.into());
+        }
+    };
+    let handler = handler_fn(|event, _: Context| handle(event, &config));
 
-    lambda_runtime::run(handler).await?;
+    // Invocation errors go back to the runtime API from in here, this only returns if the
+    // runtime itself falls over
+    lambda_runtime::run(handler).await
+}
 
-    Ok(()) // FIXME: This can never return an Error
+/// Function entrypoint for the Lambda runtime, and for the `finding` CLI
+///
+/// Findings arrive either from an SNS subscription or straight from an EventBridge rule. SNS can
+/// deliver several records in one invocation, each one is handled independently so a bad record
+/// doesn't stop the rest from being sent.
+///
+/// Every send, and everything PagerDuty and Sentry are told, is awaited before this returns, and
+/// the logs and metrics are flushed, so nothing is lost if the container is frozen or recycled
+/// straight after.
+pub async fn handle(event: Value, config: &Config) -> Result<Value, Error> {
+    let result = process(event, config).await;
+    logging::flush();
+    result
 }
 
-/// Function entrypoint for the Lambda runtime
-async fn handler(event: SnsEvent, _: Context) -> Result<Value, Error> {
-    let message: Message = serde_json::from_str(&event.records[0].sns.message.as_ref().unwrap())
-        .expect("Failed to deserialize message, wrong format.");
+async fn process(event: Value, config: &Config) -> Result<Value, Error> {
+    if is_warmup(&event) {
+        log::debug!("Warmup ping, nothing to do but say what rate limits held back");
+        post_held_back(config).await;
+        return Ok(json!({ "message": "Warm" }));
+    }
+
+    // Nothing to send, SNS just wants to know we're listening
+    if let Some((url, topic_arn)) = subscribe_url(&event) {
+        if !is_sns_subscribe_url(url, topic_arn) {
+            log::warn!(
+                "WARN: Not confirming a subscription to {:?}, its SubscribeURL isn't one SNS sends",
+                topic_arn
+            );
+            return Ok(json!({ "message": "Subscription not confirmed" }));
+        }
+        confirm_subscription(url, &config.retry).await?;
+        return Ok(json!({ "message": "Subscription confirmed" }));
+    }
+
+    let findings = match parse_event(event) {
+        Ok(findings) => findings,
+        Err(e) => {
+            log::error!(error_type = e.error_type(); "ERR: {}", e);
+            report(&e, config).await;
+            return Err(e.into());
+        }
+    };
+    let mut summary = Summary::new(findings.len());
+    let mut first_error = None;
+    let mut messages = vec![];
+
+    for finding in findings {
+        let mut message = match finding {
+            Ok(Record::Finding(message)) => *message,
+            Ok(Record::Other(event)) => {
+                log::warn!(
+                    "WARN: Ignoring {}, it's a {} event from {} rather than a GuardDuty finding, check what's subscribed to the topic",
+                    event["id"],
+                    event["detail-type"],
+                    event["source"]
+                );
+                let id = event.pointer("/detail/id").and_then(Value::as_str);
+                summary.skipped_id(id.map(String::from), "not_guardduty");
+                continue;
+            }
+            Err(e) => {
+                log::error!(error_type = e.error_type(); "ERR: {}", e);
+                report(&e, config).await;
+                let id = match &e {
+                    AppError::Deserialize { id, .. } => id.clone(),
+                    _ => None,
+                };
+                summary.failed(id, e.to_string());
+                first_error.get_or_insert(e);
+                continue;
+            }
+        };
+
+        emf::emit_finding_metric(&message, config);
+
+        // Archived findings still go to PagerDuty, to resolve the incident
+        let skip = message.skip_reason(config);
+        if let (Some(pagerduty), None | Some(Skip::Archived)) = (&config.pagerduty, &skip) {
+            pagerduty.notify(&message, config).await;
+        }
+
+        if let Some(skip) = skip {
+            let (finding_id, severity) = (message.detail.id.as_str(), message.detail.severity);
+            match skip {
+                Skip::Archived => log::info!(
+                    finding_id, severity;
+                    "Filtered {}, finding is archived",
+                    message.detail.id
+                ),
+                Skip::BelowMinSeverity => log::info!(
+                    finding_id, severity;
+                    "Filtered {}, severity {} is below MIN_SEVERITY {}",
+                    message.detail.id,
+                    message.detail.severity,
+                    config.min_severity
+                ),
+                Skip::SuppressedType => log::debug!(
+                    finding_id, severity;
+                    "Filtered {}, {} is in SUPPRESS_TYPES",
+                    message.detail.id,
+                    message.detail.tipe
+                ),
+                Skip::AccountNotAllowed => log::debug!(
+                    finding_id, severity;
+                    "Filtered {}, account {} isn't in ACCOUNT_ALLOWLIST",
+                    message.detail.id,
+                    message.detail.account_id
+                ),
+            }
+            summary.skipped(&message, skip.as_str());
+            continue;
+        }
+
+        if let Some(quiet) = &config.quiet_hours {
+            if quiet.holds_back(&message, config.levels.thresholds.critical, Utc::now()) {
+                log::info!(
+                    finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                    "Filtered {}, severity {} during quiet hours",
+                    message.detail.id,
+                    message.detail.severity
+                );
+                summary.skipped(&message, "quiet_hours");
+                continue;
+            }
+        }
+
+        if let Some(recent) = &config.recent {
+            if recent.already_seen(&message) {
+                log::info!(
+                    finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                    "Filtered {}, this container sent it moments ago",
+                    message.detail.id
+                );
+                summary.skipped(&message, "recently_sent");
+                continue;
+            }
+        }
+
+        if let Some(dedupe) = &config.dedupe {
+            if dedupe.already_sent(&message).await {
+                log::info!(
+                    finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                    "Filtered {}, already sent this update",
+                    message.detail.id
+                );
+                summary.skipped(&message, "already_sent");
+                continue;
+            }
+        }
+
+        if let Some(transitions) = &config.transitions {
+            let band = &config.levels.from_severity(message.detail.severity).name;
+            if !transitions.is_transition(&message, band).await {
+                log::info!(
+                    finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                    "Filtered {}, still {} since it was last posted",
+                    message.detail.id,
+                    band
+                );
+                summary.skipped(&message, "no_transition");
+                continue;
+            }
+        }
+
+        if let Some(rate_limit) = &config.rate_limit {
+            let to = Route {
+                webhook: config.webhook_for(&message).to_string(),
+                channel: config.channel_for(message.level(config)).map(String::from),
+            };
+            match rate_limit.check(&message, &to, Utc::now()) {
+                Limit::Post { suppressed } => message.suppressed = suppressed,
+                Limit::Suppress => {
+                    log::info!(
+                        finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                        "Filtered {}, too many {} findings, RATE_LIMIT_COUNT reached",
+                        message.detail.id,
+                        message.detail.tipe
+                    );
+                    summary.skipped(&message, "rate_limited");
+                    // Not posted, so a redelivery or the same band change later still counts
+                    forget(&message, config).await;
+                    continue;
+                }
+            }
+        }
+
+        messages.push(message);
+    }
+
+    if config.batch_mode && !messages.is_empty() {
+        // Findings are batched by where each one would have gone on its own, the webhook for its
+        // account or band and the channel for its band, so an account's workspace only sees its
+        // own findings and a low one never rides along into the critical channel. Most severe
+        // first, that one carries the only mention.
+        let destination =
+            |m: &Message| (config.webhook_for(m), config.channel_for(m.level(config)));
+        messages.sort_by(|a, b| {
+            destination(a)
+                .cmp(&destination(b))
+                .then(b.detail.severity.total_cmp(&a.detail.severity))
+        });
+        let batches = messages
+            .chunk_by(|a, b| destination(a) == destination(b))
+            // A burst is split over several messages rather than Slack rejecting one huge one
+            .flat_map(|findings| findings.chunks(config.batch_size()))
+            .flat_map(|findings| split(findings, config));
+
+        for batch in batches {
+            let webhook = config.webhook_for(&batch[0]);
+            log::debug!("Sending a batch of {} to {}", batch.len(), webhook);
+
+            let ids = batch
+                .iter()
+                .map(|m| &*m.detail.id)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // A batch covers several findings, so it can't be a reply to any one of their threads
+            let body = render(batch, config);
+            let result = config
+                .poster()
+                .post(&ids, webhook, body, None, &config.retry)
+                .await;
+
+            match result {
+                Ok(_) => batch.iter().for_each(|message| summary.sent(message)),
+                Err(e) => {
+                    report(&e, config).await;
+                    for message in batch {
+                        summary.failed(Some(message.detail.id.clone()), e.to_string());
+                        unsent(message, config).await;
+                    }
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+    } else {
+        for message in &messages {
+            let webhook = config.webhook_for(message);
+            log::debug!(
+                finding_id = message.detail.id.as_str(), severity = message.detail.severity;
+                "Sending {} to {}",
+                message.detail.id,
+                webhook
+            );
+
+            let id = &message.detail.id;
+            let thread = match &config.bot {
+                Some(bot) => bot.thread_for(message).await,
+                None => None,
+            };
+            let body = render(std::slice::from_ref(message), config);
+            let result = config
+                .poster()
+                .post(id, webhook, body, thread.as_deref(), &config.retry)
+                .await;
+
+            // Only the first message for a finding starts a thread
+            if let (Some(bot), Ok(response), None) = (&config.bot, &result, &thread) {
+                bot.remember(message, response).await;
+            }
+
+            match result {
+                Ok(_) => summary.sent(message),
+                Err(e) => {
+                    report(&e, config).await;
+                    summary.failed(Some(message.detail.id.clone()), e.to_string());
+                    first_error.get_or_insert(e);
+                    unsent(message, config).await;
+                }
+            }
+        }
+    }
+
+    // The types above have had their windows checked, any other window that's up won't get a
+    // later finding to say what it held back
+    post_held_back(config).await;
 
-    let webhook =
-        env::var("WEBHOOK_URL").expect("ERR: WEBHOOK_URL environment variable not set, fatal");
+    log::info!(
+        "Processed {} findings, {} sent, {} skipped, {} failed",
+        summary.total,
+        summary.sent,
+        summary.skipped,
+        summary.failed
+    );
 
-    log::debug!("WEBHOOK_URL={}", webhook);
+    // Every record gets a chance to send, but a finding that couldn't be read or sent still has
+    // to fail the invocation so it shows up in the Lambda error metrics
+    if let Some(e) = first_error {
+        return Err(AppError::Failed {
+            failed: summary.failed,
+            total: summary.total,
+            first: Box::new(e),
+        }
+        .into());
+    }
 
-    send(&webhook, message.build_payload()).await;
+    Ok(json!(summary))
+}
+
+/// What became of every finding in an invocation, the handler's response when none failed. A
+/// Step Functions state or a test can check this rather than digging through the logs.
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub message: &'static str,
+    pub total: usize,
+    pub sent: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub findings: Vec<FindingOutcome>,
+}
 
-    Ok(json!({ "message": format!("OK") }))
+#[derive(Serialize, Debug)]
+pub struct FindingOutcome {
+    /// None for a finding that couldn't be read far enough to find its id
+    pub id: Option<String>,
+    pub outcome: Outcome,
+    /// Why it was skipped, e.g. "below_min_severity", or the error it failed with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
-async fn send(webhook: &str, p: Payload) {
-    let slack = Slack::new(webhook).unwrap();
-    let res = slack.send(&p).await;
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Sent,
+    Skipped,
+    Failed,
+}
+
+impl Summary {
+    fn new(total: usize) -> Summary {
+        Summary {
+            message: "OK",
+            total,
+            sent: 0,
+            skipped: 0,
+            failed: 0,
+            findings: Vec::with_capacity(total),
+        }
+    }
+
+    fn sent(&mut self, message: &Message) {
+        self.sent += 1;
+        self.record(Some(message.detail.id.clone()), Outcome::Sent, None);
+    }
+
+    fn skipped(&mut self, message: &Message, reason: &str) {
+        self.skipped_id(Some(message.detail.id.clone()), reason);
+    }
+
+    /// For a record that's skipped before it's read far enough to be a [`Message`]
+    fn skipped_id(&mut self, id: Option<String>, reason: &str) {
+        self.skipped += 1;
+        self.record(id, Outcome::Skipped, Some(reason.to_string()));
+    }
 
-    // Logs an error if Slack rejects the message or goes down but...
-    // FIXME: Ensure Lambda returns 500 or panics to guarantee it hits the error metrics
-    match res {
-        Ok(()) => log::info!("Message sent to Slack"),
-        Err(e) => log::error!("ERR: {:?}", e),
+    fn failed(&mut self, id: Option<String>, reason: String) {
+        self.failed += 1;
+        self.record(id, Outcome::Failed, Some(reason));
+    }
+
+    fn record(&mut self, id: Option<String>, outcome: Outcome, reason: Option<String>) {
+        self.findings.push(FindingOutcome {
+            id,
+            outcome,
+            reason,
+        });
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Message {
-    version: String,
-    id: String,
-    #[serde(rename(deserialize = "detail-type"))]
-    detail_type: String,
-    source: String,
-    account: String,
-    time: DateTime<Utc>,
-    region: String,
-    resources: Value,
-    detail: Detail,
+/// A record as it was read, a finding or an event from something other than GuardDuty
+enum Record {
+    Finding(Box<Message>),
+    /// Left as it arrived, it's only looked at to say what it was
+    Other(Value),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct Detail {
-    schema_version: String,
-    account_id: String,
-    region: String,
-    partition: String,
-    id: String,
-    arn: String,
-    #[serde(rename(deserialize = "type"))]
-    tipe: String, // Type is a reserved Rust word, so we misspell it
-    resource: Value,
-    service: Service,
-    severity: f32,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
-    title: String,
-    description: String,
+/// Unwraps the findings from whichever envelope they arrived in. SNS carries each finding as a JSON
+/// string inside a record, EventBridge delivers the finding as the event itself.
+fn parse_event(event: Value) -> Result<Vec<Result<Record, AppError>>, AppError> {
+    if event.get("Records").is_some() {
+        let raw = event.to_string();
+        let event: SnsEvent =
+            serde_json::from_str(&raw).map_err(|e| AppError::deserialize(&raw, e))?;
+        Ok(event
+            .records
+            .iter()
+            .filter(|record| match record.sns.type_.as_deref() {
+                None | Some("Notification") => true,
+                Some(other) => {
+                    log::info!("Ignoring SNS {} message", other);
+                    false
+                }
+            })
+            // Nothing to retry in a record with no message, so it's skipped rather than failed
+            .filter_map(|record| match record.sns.message.as_deref() {
+                Some(message) if !message.trim().is_empty() => Some(parse_message(message)),
+                _ => {
+                    log::warn!(
+                        "WARN: Ignoring SNS message {}, it has no Message",
+                        record.sns.message_id.as_deref().unwrap_or("with no id")
+                    );
+                    None
+                }
+            })
+            .collect())
+    } else {
+        Ok(vec![parse_message(&event.to_string())])
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct Service {
-    service_name: String,
-    detector_id: String,
-    action: Value,
-    resource_role: String,
-    additional_info: Value,
-    event_first_seen: DateTime<Utc>,
-    event_last_seen: DateTime<Utc>,
-    archived: bool,
-    count: usize,
+/// Keep-warm pings, either `{"warmup": true}` or an EventBridge schedule pointed at the function
+fn is_warmup(event: &Value) -> bool {
+    event["warmup"] == true
+        || (event["source"] == "aws.events" && event["detail-type"] == "Scheduled Event")
 }
 
-impl Message {
-    /// Sets up the Slack payload, constructed loosely around the Slack BlockKit format.
-    /// Note you can't repeat things, like .text().text()
-    ///
-    /// This is built using the excellent slack-hook3 crate, this is a branch of a fork of a fork because
-    /// the original maintainer doesn't look after slack-hook any more.
-    /// https://github.com/0xc0deface/rust-slack/tree/v3
-
-    fn build_payload(&self) -> Payload {
-        let levels = Levels::default();
-        let level = levels.from_severity(self.detail.severity);
-
-        let fallback = format!(
-            "GuardDuty:{} in {} {}",
-            self.detail.tipe, self.detail.account_id, self.detail.region
-        );
+/// The URL to visit and the topic it's for if this is an SNS SubscriptionConfirmation, either
+/// inside a record or as the bare SNS message (when it's been passed on over HTTPS)
+fn subscribe_url(event: &Value) -> Option<(&str, Option<&str>)> {
+    let is_confirmation = |sns: &Value| sns["Type"] == "SubscriptionConfirmation";
+
+    let sns = match event.get("Records") {
+        Some(records) => records
+            .as_array()?
+            .iter()
+            .map(|record| &record["Sns"])
+            .find(|sns| is_confirmation(sns))?,
+        None if is_confirmation(event) => event,
+        None => return None,
+    };
+
+    Some((sns["SubscribeURL"].as_str()?, sns["TopicArn"].as_str()))
+}
 
-        let fields = vec![
-            Field {
-                title: String::from("Severity"),
-                value: self.detail.severity.to_string().into(),
-                short: Some(true),
-            },
-            Field {
-                title: String::from("First seen"),
-                value: self
-                    .detail
-                    .service
-                    .event_first_seen
-                    .format("%a %b %e %T")
-                    .to_string()
-                    .into(),
-                short: Some(true),
-            },
-            Field {
-                title: String::from("Count"),
-                value: self.detail.service.count.to_string().into(),
-                short: Some(true),
-            },
-            Field {
-                title: String::from("Last seen"),
-                value: self
-                    .detail
-                    .service
-                    .event_last_seen
-                    .format("%a %b %e %T")
-                    .to_string()
-                    .into(),
-                short: Some(true),
-            },
-        ];
-        let a = AttachmentBuilder::new(fallback)
-            .color(&*level.colour)
-            .pretext(format!(
-                "*Finding in {} from account {}* {}",
-                &self.detail.region, &self.detail.account_id, &*level.mention
-            ))
-            .title(&*self.detail.tipe)
-            .title_link(&self.finding_link())
-            .text(&*self.detail.description)
-            .fields(fields)
-            .footer("GuardyBot")
-            .footer_icon("https://rustacean.net/assets/rustacean-flat-happy.png")
-            .ts(&self.detail.updated_at.naive_local())
-            .build()
-            .expect("ERR: Failed to build Slack attachment");
-
-        PayloadBuilder::new()
-            .attachments(vec![a])
-            .link_names(true)
-            .build()
-            .expect("ERR: Failed to build Slack payload")
+/// Whether `url` is a SubscribeURL SNS would send: https, on the SNS endpoint for the topic's
+/// region and confirming that topic. Anyone who can put an event in front of the Lambda could
+/// otherwise have it fetch any URL they like, from inside its VPC and with its role.
+pub fn is_sns_subscribe_url(url: &str, topic_arn: Option<&str>) -> bool {
+    let url = match reqwest::Url::parse(url) {
+        Ok(url) if url.scheme() == "https" && url.port().is_none() => url,
+        _ => return false,
+    };
+    let region = match url
+        .host_str()
+        .and_then(|host| host.strip_prefix("sns."))
+        .and_then(|host| {
+            host.strip_suffix(".amazonaws.com")
+                .or_else(|| host.strip_suffix(".amazonaws.com.cn"))
+        }) {
+        Some(region) if !region.is_empty() => region,
+        _ => return false,
+    };
+    if !region
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return false;
     }
 
-    /// Performs the required transformation to turn an AWS finding name string into the correct
-    /// URL to their GuardDuty docs. These aren't all deterministic (IAMUser links to iam.html)
-    /// Deliberately setup in a way to fail if a new finding category comes out, so we don't start sending
-    /// bad links.
-    fn finding_link(&self) -> String {
-        let finding = &self.detail.tipe;
-        let base_url = "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-";
-
-        let re = Regex::new(r"(?::)([\w]*?)(?:/)").unwrap(); // Capture "bar" from "foo:bar/baz"
-        let lower_finding = &finding.to_lowercase(); // Downcase the string
-        let caps = re.captures(lower_finding); // ec2 or None
-
-        let finding_group = match caps {
-            Some(caps) => caps.get(1),
-            None => {
-                log::error!("ERR: Couldn't match a finding group in: {}", &finding);
-                return String::from("");
-            }
-        };
+    let confirms = url
+        .query_pairs()
+        .find(|(key, _)| key == "TopicArn")
+        .map(|(_, value)| value.into_owned());
+    match topic_arn {
+        // arn:aws:sns:<region>:<account>:<topic>
+        Some(arn) => arn.split(':').nth(3) == Some(region) && confirms.as_deref() == Some(arn),
+        None => confirms.is_some(),
+    }
+}
 
-        let group_str = match finding_group {
-            None => return String::from(""),
-            Some(s) if s.as_str() == "iamuser" => String::from("iam"),
-            Some(s) if s.as_str() == "ec2" => String::from("ec2"),
-            Some(s) if s.as_str() == "s3" => String::from("s3"),
-            Some(s) if s.as_str() == "kubernetes" => String::from("kubernetes"),
-            Some(s) => {
-                log::error!("ERR: Got unexpected finding group: {:#?}", s.to_owned());
-                return String::from("");
-            }
-        };
+/// SNS confirms the subscription when its SubscribeURL gets a GET
+async fn confirm_subscription(url: &str, retry: &RetryPolicy) -> Result<(), AppError> {
+    log::info!("Confirming SNS subscription");
+    let confirm_error = |reason: String| AppError::Subscription {
+        url: url.to_string(),
+        reason,
+    };
 
-        let anchor = re.replace(lower_finding, format!("-{}-", &group_str));
+    let response = reqwest::Client::new()
+        .get(url)
+        .timeout(retry.timeout)
+        .send()
+        .await
+        .map_err(|e| confirm_error(e.to_string()))?;
 
-        return format!("{}{}.html#{}", base_url, group_str, anchor);
+    if response.status().is_success() {
+        log::info!("SNS subscription confirmed");
+        Ok(())
+    } else {
+        Err(confirm_error(format!(
+            "SNS responded with HTTP {}",
+            response.status()
+        )))
     }
 }
-struct SeverityLevel<'a> {
-    colour: &'a str,
-    mention: &'a str,
+
+/// Reads one event. Anything EventBridge sent from another source, or another GuardDuty event
+/// type, won't be the shape of a finding, so it's set aside before it can fail to deserialize.
+fn parse_message(raw: &str) -> Result<Record, AppError> {
+    let event: Value = serde_json::from_str(raw).map_err(|e| AppError::deserialize(raw, e))?;
+    if let Some(source) = event["source"].as_str() {
+        if source != "aws.guardduty" || event["detail-type"] != "GuardDuty Finding" {
+            return Ok(Record::Other(event));
+        }
+    }
+
+    let message = Message::deserialize(&event).map_err(|e| AppError::deserialize(raw, e))?;
+    if !message.schema_is_known() {
+        log::warn!(
+            finding_id = message.detail.id.as_str();
+            "WARN: {} has schemaVersion {} but this was written against {:?}, some details may be missing",
+            message.detail.id,
+            message.detail.schema_version,
+            SCHEMA_VERSIONS
+        );
+    }
+    Ok(Record::Finding(Box::new(message)))
 }
 
-struct Levels<'a> {
-    critical: SeverityLevel<'a>,
-    high: SeverityLevel<'a>,
-    medium: SeverityLevel<'a>,
-    low: SeverityLevel<'a>,
-    unknown: SeverityLevel<'a>,
+/// Counts an error in the Errors metric by its type, and passes it on to Sentry when SENTRY_DSN
+/// is set, with the finding id and the raw message if they can be had
+async fn report(e: &AppError, config: &Config) {
+    emf::emit_error_metric(e);
+    let sentry = match &config.sentry {
+        Some(sentry) => sentry,
+        None => return,
+    };
+    let (finding_id, raw) = match e {
+        AppError::Deserialize { id, raw, .. } => (id.as_deref(), Some(raw.as_str())),
+        AppError::Send { id, .. } | AppError::Timeout { id, .. } | AppError::Format { id, .. } => {
+            (Some(id.as_str()), None)
+        }
+        _ => (None, None),
+    };
+    sentry
+        .capture(&e.to_string(), finding_id, raw, config.retry.timeout)
+        .await;
 }
 
-impl<'a> Default for Levels<'a> {
-    fn default() -> Levels<'a> {
-        Levels {
-            critical: SeverityLevel {
-                colour: Colour::RED,
-                mention: "@channel",
-            },
-            high: SeverityLevel {
-                colour: Colour::ORANGE,
-                mention: "@channel",
-            },
-            medium: SeverityLevel {
-                colour: Colour::YELLOW,
-                mention: "@here",
-            },
-            low: SeverityLevel {
-                colour: Colour::BLUE,
-                mention: "",
-            },
-            unknown: SeverityLevel {
-                colour: Colour::SILVER,
-                mention: "",
-            },
+/// Undoes everything that counted a finding as sent when it couldn't be, the dedupe and its place
+/// in the rate limit
+async fn unsent(message: &Message, config: &Config) {
+    forget(message, config).await;
+    if let Some(rate_limit) = &config.rate_limit {
+        rate_limit.unpost(message);
+    }
+}
+
+/// Posts a summary for each finding type whose rate limit window has ended with findings held
+/// back. One that can't be posted is kept for next time rather than failing the invocation.
+async fn post_held_back(config: &Config) {
+    let rate_limit = match &config.rate_limit {
+        Some(rate_limit) => rate_limit,
+        None => return,
+    };
+    for held_back in rate_limit.expire(Utc::now()) {
+        let id = format!("the {} held back", held_back.tipe);
+        let body = ratelimit::summary(&held_back, config);
+        let result = config
+            .poster()
+            .post(&id, &held_back.to.webhook, body, None, &config.retry)
+            .await;
+        match result {
+            Ok(_) => log::info!(
+                "{} more {} findings were held back by RATE_LIMIT_COUNT, posted a summary",
+                held_back.count,
+                held_back.tipe
+            ),
+            Err(e) => {
+                report(&e, config).await;
+                rate_limit.restore(held_back, Utc::now());
+            }
         }
     }
 }
 
-impl<'a> Levels<'a> {
-    fn from_severity(self, severity: f32) -> SeverityLevel<'a> {
-        return match severity {
-            x if x >= 9.0 && x < 10.0 => self.critical,
-            x if x >= 7.0 && x < 9.0 => self.high,
-            x if x >= 4.0 && x < 7.0 => self.medium,
-            x if x >= 1.0 && x < 4.0 => self.low,
-            _ => self.unknown,
-        };
+/// Undoes the dedupe for a finding that couldn't be sent or was rate limited, so a retry of it
+/// gets through
+async fn forget(message: &Message, config: &Config) {
+    if let Some(recent) = &config.recent {
+        recent.forget(message);
+    }
+    if let Some(dedupe) = &config.dedupe {
+        dedupe.forget(message).await;
+    }
+    if let Some(transitions) = &config.transitions {
+        transitions.forget(message).await;
+    }
+}
+
+/// Lays out single findings for Slack attachments, only ever the default one so far
+fn formatter(config: &Config) -> impl Formatter + '_ {
+    DefaultFormatter::new(config)
+}
+
+/// Splits a batch further when the destination's own limits need it. Block Kit caps blocks rather
+/// than attachments, and a finding takes several.
+fn split<'a>(messages: &'a [Message], config: &Config) -> Vec<&'a [Message]> {
+    match (config.destination, config.slack_format) {
+        (Destination::Slack, SlackFormat::Blocks) => blocks::split(messages, config),
+        _ => vec![messages],
     }
 }
 
-#[non_exhaustive]
-enum Colour {}
-#[allow(dead_code)]
-impl Colour {
-    pub const RED: &'static str = "#DF4661";
-    pub const ORANGE: &'static str = "#DB6B30";
-    pub const YELLOW: &'static str = "#FED141";
-    pub const GREEN: &'static str = "#008C95";
-    pub const BLUE: &'static str = "#00A3E0";
-    pub const SILVER: &'static str = "#BABABA";
-    pub const PINK: &'static str = "#AF1685";
-    pub const PURPLE: &'static str = "#2E1A47";
+/// Builds one message for the destination holding every finding given
+fn render(messages: &[Message], config: &Config) -> Value {
+    match config.destination {
+        Destination::Slack => match config.slack_format {
+            SlackFormat::Attachments => {
+                let payload = match messages {
+                    [message] => formatter(config).format(message),
+                    _ => build_batch_payload(messages, config),
+                };
+                // A plain message with the bare facts beats no alert at all
+                payload.map(|p| json!(p)).unwrap_or_else(|e| {
+                    log::error!(error_type = e.error_type(); "ERR: {}, sending a plain message", e);
+                    emf::emit_error_metric(&e);
+                    fallback_payload(messages, config)
+                })
+            }
+            SlackFormat::Blocks => blocks::payload(messages, config),
+        },
+        Destination::Teams => teams::card(messages, config),
+        Destination::Generic => generic::body(messages, config),
+    }
 }
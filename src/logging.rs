@@ -0,0 +1,84 @@
+//! Log setup. Plaintext by default, or one JSON object per line with LOG_FORMAT=json so CloudWatch
+//! Logs Insights can filter on fields without a regex. Log calls about a finding attach
+//! `finding_id` and `severity` as key-values, those become keys of their own in JSON.
+
+use crate::settings;
+use log::kv::{Error, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Map};
+use simple_logger::SimpleLogger;
+use std::env;
+use std::io::{self, Write};
+
+/// Takes log level from RUST_LOG [off, error, warn, info, debug, trace]
+/// https://docs.rs/env_logger/latest/env_logger/#enabling-logging
+///
+/// LOG_FORMAT can be in CONFIG_JSON like any other setting, so that's read first. Bad JSON in it
+/// is left for `Config::from_env` to report, once there's a logger to report it with.
+pub fn init() {
+    let _ = settings::load();
+    if settings::var("LOG_FORMAT").is_ok_and(|f| f.eq_ignore_ascii_case("json")) {
+        let level = env::var("RUST_LOG")
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(LevelFilter::Info);
+        log::set_boxed_logger(Box::new(JsonLogger { level })).unwrap();
+        log::set_max_level(level);
+    } else {
+        SimpleLogger::new()
+            .env()
+            .with_level(LevelFilter::Info)
+            .without_timestamps()
+            .init()
+            .unwrap();
+    }
+}
+
+/// Writes out anything the logger or stdout are still holding. EMF metrics go straight to stdout,
+/// so this covers them too.
+pub fn flush() {
+    log::logger().flush();
+    let _ = io::stdout().flush();
+}
+
+struct JsonLogger {
+    level: LevelFilter,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut line = Map::new();
+        line.insert(String::from("level"), json!(record.level().as_str()));
+        line.insert(String::from("message"), json!(record.args().to_string()));
+        // Nothing useful to do if a key-value can't be read, the message is still worth having
+        let _ = record.key_values().visit(&mut Fields(&mut line));
+
+        println!("{}", serde_json::Value::Object(line));
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Copies key-values onto the JSON line, keeping numbers as numbers
+struct Fields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = match value.to_f64() {
+            Some(number) => json!(number),
+            None => json!(value.to_string()),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! The GuardDuty finding as it arrives from EventBridge, deserialized as is. Everything done
+//! with a finding is in `impl Message` elsewhere, these are just the fields.
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A GuardDuty finding as delivered by EventBridge, a.k.a. CloudWatch Events
+/// https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_findings_cloudwatch.html
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Message {
+    pub version: String,
+    pub id: String,
+    #[serde(rename = "detail-type")]
+    pub detail_type: String,
+    pub source: String,
+    pub account: String,
+    pub time: DateTime<Utc>,
+    pub region: String,
+    pub resources: Value,
+    pub detail: Detail,
+    /// How many findings of this type RATE_LIMIT_COUNT held back before this one
+    #[serde(skip)]
+    pub suppressed: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Detail {
+    pub schema_version: String,
+    pub account_id: String,
+    pub region: String,
+    pub partition: String,
+    pub id: String,
+    pub arn: String,
+    #[serde(rename = "type")]
+    pub tipe: String, // Type is a reserved Rust word, so we misspell it
+    pub resource: Value,
+    pub service: Service,
+    #[serde(default = "missing_severity")]
+    pub severity: f32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub title: String,
+    pub description: String,
+    /// Anything newer schema versions add, kept so VERBOSE still shows the finding as sent
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The commonly needed parts of `resource.instanceDetails` when `resourceType` is "Instance", so
+/// they don't have to be dug out of the JSON each time. Everything else is left in the Value.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Ec2Resource {
+    pub instance_id: String,
+    pub instance_type: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub network_interfaces: Vec<NetworkInterface>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Tag {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInterface {
+    pub network_interface_id: Option<String>,
+    pub private_ip_address: Option<String>,
+    pub public_ip: Option<String>,
+    pub subnet_id: Option<String>,
+    pub vpc_id: Option<String>,
+}
+
+/// A finding with no severity is still worth sending, it just ends up as unknown
+fn missing_severity() -> f32 {
+    log::warn!("WARN: Finding has no severity, treating it as unknown");
+    0.0
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Service {
+    pub service_name: String,
+    pub detector_id: String,
+    /// Malware Protection findings come from a scan rather than something the resource did, so
+    /// they've no action, just the scan results
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub action: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub ebs_volume_scan_details: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub malware_scan_details: Value,
+    pub resource_role: String,
+    pub additional_info: Value,
+    pub event_first_seen: DateTime<Utc>,
+    pub event_last_seen: DateTime<Utc>,
+    pub archived: bool,
+    pub count: usize,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The finding schema versions this was written against. Unknown fields in any version are
+/// ignored, so a newer one only gets a warning.
+pub const SCHEMA_VERSIONS: &[&str] = &["2.0"];
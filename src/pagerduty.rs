@@ -0,0 +1,113 @@
+//! Pages through the PagerDuty Events API v2 as well as posting to chat, for findings severe
+//! enough to wake someone up. Set with PAGERDUTY_ROUTING_KEY, the integration key of an Events
+//! API v2 integration on the service.
+//! https://developer.pagerduty.com/docs/events-api-v2/trigger-events/
+
+use crate::{env_parse, send, settings, Config, Message};
+use serde_json::{json, Value};
+
+const ENQUEUE_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+pub struct PagerDuty {
+    routing_key: String,
+    min_severity: f32,
+}
+
+impl PagerDuty {
+    /// PAGERDUTY_MIN_SEVERITY is the lowest severity that pages, critical findings only by default
+    pub fn from_env(critical: f32) -> Option<PagerDuty> {
+        let routing_key = settings::var("PAGERDUTY_ROUTING_KEY").ok()?;
+        Some(PagerDuty {
+            routing_key,
+            min_severity: env_parse("PAGERDUTY_MIN_SEVERITY", critical),
+        })
+    }
+
+    /// Triggers an incident for a severe finding (unless it's a sample), or resolves it once the
+    /// finding is archived. The finding id is the dedup key, so redeliveries and updates land on
+    /// the same incident. A failure is only logged, it shouldn't stop the finding going to chat.
+    pub async fn notify(&self, message: &Message, config: &Config) {
+        // Samples are generated on request, nobody needs waking up for them
+        if message.detail.severity < self.min_severity || message.is_sample() {
+            return;
+        }
+
+        let event = if message.detail.service.archived {
+            resolve_event(message, &self.routing_key)
+        } else {
+            trigger_event(message, config, &self.routing_key)
+        };
+
+        if let Err(e) = send(
+            &message.detail.id,
+            "PagerDuty",
+            ENQUEUE_URL,
+            None,
+            event,
+            &config.retry,
+        )
+        .await
+        {
+            log::error!(
+                finding_id = message.detail.id.as_str();
+                "ERR: Failed to notify PagerDuty: {}",
+                e
+            );
+        }
+    }
+}
+
+pub fn trigger_event(message: &Message, config: &Config, routing_key: &str) -> Value {
+    let detail = &message.detail;
+    let level = config.levels.from_severity(detail.severity);
+    let severity = match level.name.as_str() {
+        "Critical" => "critical",
+        "High" => "error",
+        "Medium" => "warning",
+        _ => "info",
+    };
+
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": detail.id,
+        "payload": {
+            "summary": format!("{}: {}", detail.tipe, detail.title),
+            "source": format!(
+                "{} {}",
+                message.account_label(&config.account_names),
+                detail.region
+            ),
+            "severity": severity,
+            "timestamp": detail.updated_at.to_rfc3339(),
+            "component": detail.resource["resourceType"],
+            "group": detail.account_id,
+            "class": detail.tipe,
+            "custom_details": {
+                "description": detail.description,
+                "severity": detail.severity,
+                "count": detail.service.count,
+            },
+        },
+        "links": [
+            { "href": message.console_link(), "text": "View finding" },
+            { "href": message.finding_link(), "text": "Finding type docs" },
+        ],
+    })
+}
+
+fn resolve_event(message: &Message, routing_key: &str) -> Value {
+    json!({
+        "routing_key": routing_key,
+        "event_action": "resolve",
+        "dedup_key": message.detail.id,
+    })
+}
+
+impl Message {
+    /// The PagerDuty Events API v2 trigger for the finding, as sent when PAGERDUTY_ROUTING_KEY is
+    /// set
+    pub fn build_pagerduty_event(&self, config: &Config, routing_key: &str) -> Value {
+        trigger_event(self, config, routing_key)
+    }
+}
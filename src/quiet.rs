@@ -0,0 +1,82 @@
+//! Optional quiet hours, set with QUIET_HOURS_START and QUIET_HOURS_END as "HH:MM" times of day.
+//! Sub-critical findings that arrive in the window are logged instead of posted, so nobody gets
+//! woken at 3am by a port probe. Critical findings always go through.
+
+use crate::{display_tz_from_env, settings, Message};
+use chrono::prelude::*;
+use chrono_tz::Tz;
+
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    tz: Tz,
+}
+
+impl QuietHours {
+    /// An `end` before `start` runs over midnight, e.g. 22:00 to 07:00
+    pub fn new(start: NaiveTime, end: NaiveTime, tz: Tz) -> QuietHours {
+        QuietHours { start, end, tz }
+    }
+
+    /// None unless both times are set and parse. The times are in QUIET_HOURS_TZ, or DISPLAY_TZ
+    /// when that isn't set.
+    pub fn from_env() -> Option<QuietHours> {
+        let (start, end) = match (
+            settings::var("QUIET_HOURS_START"),
+            settings::var("QUIET_HOURS_END"),
+        ) {
+            (Ok(start), Ok(end)) => (start, end),
+            (Err(_), Err(_)) => return None,
+            _ => {
+                log::warn!(
+                    "WARN: QUIET_HOURS_START and QUIET_HOURS_END need setting together, ignoring them"
+                );
+                return None;
+            }
+        };
+
+        let (start, end) = match (time_of_day(&start), time_of_day(&end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                log::warn!(
+                    "WARN: QUIET_HOURS_START={:?} or QUIET_HOURS_END={:?} isn't an HH:MM time, no quiet hours",
+                    start,
+                    end
+                );
+                return None;
+            }
+        };
+
+        let tz = match settings::var("QUIET_HOURS_TZ") {
+            Err(_) => display_tz_from_env(),
+            Ok(name) => name.parse().unwrap_or_else(|_| {
+                log::warn!(
+                    "WARN: QUIET_HOURS_TZ={:?} is not a known time zone, using UTC",
+                    name
+                );
+                Tz::UTC
+            }),
+        };
+
+        Some(QuietHours::new(start, end, tz))
+    }
+
+    /// Whether `now` falls in the window, which includes its start but not its end
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.tz).time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// True for a finding below `critical` severity during quiet hours
+    pub fn holds_back(&self, message: &Message, critical: f32, now: DateTime<Utc>) -> bool {
+        message.detail.severity < critical && self.contains(now)
+    }
+}
+
+fn time_of_day(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").ok()
+}
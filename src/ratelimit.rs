@@ -0,0 +1,190 @@
+//! Optional rate limiting of bursts of the same finding type, set with RATE_LIMIT_COUNT. A scan
+//! can raise dozens of near identical findings at once, after the first few the rest only add
+//! noise. Counts are kept in the warm container, so each container limits on its own.
+
+use crate::{env_parse, settings, Config, Destination, Message};
+use chrono::prelude::*;
+use chrono::Duration;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub struct RateLimit {
+    max_posts: usize,
+    window: Duration,
+    types: Mutex<HashMap<String, Window>>,
+}
+
+/// What's happened to one finding type since `started`
+struct Window {
+    started: DateTime<Utc>,
+    posted: usize,
+    suppressed: usize,
+    /// Where the last finding held back would have gone
+    to: Route,
+}
+
+/// Where a finding would be posted, the webhook and channel its account and band route it to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Route {
+    pub webhook: String,
+    pub channel: Option<String>,
+}
+
+/// The findings of one type held back in a window that's ended, with no later finding of the
+/// type to say how many there were
+#[derive(Debug, PartialEq)]
+pub struct HeldBack {
+    pub tipe: String,
+    pub count: usize,
+    pub to: Route,
+}
+
+/// Whether a finding can be posted
+#[derive(Debug, PartialEq)]
+pub enum Limit {
+    /// Go ahead, mentioning how many of its type were held back in the window before
+    Post {
+        suppressed: usize,
+    },
+    Suppress,
+}
+
+impl RateLimit {
+    pub fn new(max_posts: usize, window: Duration) -> RateLimit {
+        RateLimit {
+            max_posts,
+            window,
+            types: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// None unless RATE_LIMIT_COUNT is set, that many findings of a type are posted in every
+    /// RATE_LIMIT_WINDOW_SECS (five minutes by default) and the rest held back
+    pub fn from_env() -> Option<RateLimit> {
+        settings::var("RATE_LIMIT_COUNT").ok()?;
+        let max_posts = env_parse("RATE_LIMIT_COUNT", 0);
+        if max_posts == 0 {
+            log::warn!("WARN: RATE_LIMIT_COUNT should be at least 1, not rate limiting");
+            return None;
+        }
+
+        Some(RateLimit::new(
+            max_posts,
+            Duration::seconds(env_parse("RATE_LIMIT_WINDOW_SECS", 300)),
+        ))
+    }
+
+    /// Counts the finding against its type's window, `to` being where it would be posted. The
+    /// first finding after a window with suppressions starts the next one, and carries the count
+    /// of what was held back.
+    pub fn check(&self, message: &Message, to: &Route, now: DateTime<Utc>) -> Limit {
+        let mut types = self.types.lock().unwrap_or_else(|e| e.into_inner());
+        let window = types
+            .entry(message.detail.tipe.clone())
+            .or_insert_with(|| Window {
+                started: now,
+                posted: 0,
+                suppressed: 0,
+                to: to.clone(),
+            });
+
+        let mut held_back = 0;
+        if now - window.started >= self.window {
+            held_back = window.suppressed;
+            *window = Window {
+                started: now,
+                posted: 0,
+                suppressed: 0,
+                to: to.clone(),
+            };
+        }
+
+        if window.posted < self.max_posts {
+            window.posted += 1;
+            Limit::Post {
+                suppressed: held_back,
+            }
+        } else {
+            window.suppressed += 1;
+            window.to = to.clone();
+            Limit::Suppress
+        }
+    }
+
+    /// Hands back the post a finding was counted for once it couldn't be sent, so only findings
+    /// that reached the channel use up the window. Any count it carried is held again, for the
+    /// window's summary.
+    pub fn unpost(&self, message: &Message) {
+        let mut types = self.types.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(window) = types.get_mut(&message.detail.tipe) {
+            window.posted = window.posted.saturating_sub(1);
+            window.suppressed += message.suppressed;
+        }
+    }
+
+    /// Drops the windows that are up, returning what was held back in them. Once a window's gone
+    /// the next finding of its type can't carry the count, so this is only for types nothing's
+    /// come in for since.
+    pub fn expire(&self, now: DateTime<Utc>) -> Vec<HeldBack> {
+        let mut types = self.types.lock().unwrap_or_else(|e| e.into_inner());
+        let mut held_back = Vec::new();
+        types.retain(|tipe, window| {
+            if now - window.started < self.window {
+                return true;
+            }
+            if window.suppressed > 0 {
+                held_back.push(HeldBack {
+                    tipe: tipe.clone(),
+                    count: window.suppressed,
+                    to: window.to.clone(),
+                });
+            }
+            false
+        });
+        held_back.sort_unstable_by(|a, b| a.tipe.cmp(&b.tipe));
+        held_back
+    }
+
+    /// Puts back what `expire` returned when the summary of it couldn't be posted, for the next
+    /// finding of the type to carry or the next summary to try again
+    pub fn restore(&self, held_back: HeldBack, now: DateTime<Utc>) {
+        let mut types = self.types.lock().unwrap_or_else(|e| e.into_inner());
+        let HeldBack { tipe, count, to } = held_back;
+        let window = types.entry(tipe).or_insert_with(|| Window {
+            started: now - self.window,
+            posted: 0,
+            suppressed: 0,
+            to,
+        });
+        window.suppressed += count;
+    }
+}
+
+/// The message saying how many findings of a type were held back, for when no later finding of
+/// the type has come in to say so
+pub(crate) fn summary(held_back: &HeldBack, config: &Config) -> Value {
+    let text = format!(
+        "{} more {} findings were held back, RATE_LIMIT_COUNT was reached",
+        held_back.count, held_back.tipe
+    );
+    match config.destination {
+        Destination::Slack => {
+            let mut payload = json!({ "text": text });
+            if let Some(channel) = &held_back.to.channel {
+                payload["channel"] = json!(channel);
+            }
+            payload
+        }
+        Destination::Teams => json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": text,
+            "text": text,
+        }),
+        Destination::Generic => json!({
+            "type": held_back.tipe,
+            "suppressed": held_back.count,
+        }),
+    }
+}
@@ -0,0 +1,263 @@
+//! Posting to Slack, or whichever webhook the destination has, with retries and a timeout.
+
+use crate::{env_bool, env_parse, AppError, Destination};
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// A post on its way out, boxed so which [`Poster`] sends it can be decided at runtime
+pub(crate) type Posting<'a> = Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>>;
+
+/// Something a rendered message can be posted through, a webhook or a Slack bot token
+pub(crate) trait Poster: Send + Sync {
+    /// Posts `body` for the finding, or findings, in `id`. Only a bot can reply in a `thread`,
+    /// and only its response has the `ts` of the message it posted.
+    fn post<'a>(
+        &'a self,
+        id: &'a str,
+        webhook: &'a str,
+        body: Value,
+        thread: Option<&'a str>,
+        retry: &'a RetryPolicy,
+    ) -> Posting<'a>;
+}
+
+/// Posts to the webhook the finding was routed to, the payload as it is
+pub(crate) struct Webhook(pub Destination);
+
+impl Poster for Webhook {
+    fn post<'a>(
+        &'a self,
+        id: &'a str,
+        webhook: &'a str,
+        body: Value,
+        _thread: Option<&'a str>,
+        retry: &'a RetryPolicy,
+    ) -> Posting<'a> {
+        Box::pin(send(id, self.0.name(), webhook, None, body, retry))
+    }
+}
+
+/// Posts the payload to the webhook, retrying rate limits (429) and server errors (5xx) with
+/// exponential backoff, and other 4xx too with SLACK_RETRY_ON_4XX. Anything else is logged here
+/// and then bubbled up so the invocation is marked as failed. `to` names who's on the other end
+/// in the logs and errors. With a bot `token` this is a Slack Web API call instead, and the JSON
+/// it responds with is returned.
+///
+/// Setting `DRY_RUN=true` (or `DRY_RUN=1`) logs the payload JSON instead of posting it, for trying
+/// out real events without spamming a channel.
+pub(crate) async fn send(
+    id: &str,
+    to: &'static str,
+    webhook: &str,
+    token: Option<&str>,
+    p: Value,
+    retry: &RetryPolicy,
+) -> Result<Value, AppError> {
+    if env_bool("DRY_RUN", false) {
+        log::info!("DRY_RUN, not sending {}: {}", id, p);
+        return Ok(Value::Null);
+    }
+
+    let client = reqwest::Client::new();
+    let mut attempt = 0;
+    let mut waited = Duration::from_secs(0);
+
+    loop {
+        // A hung webhook isn't retried, it would likely hang again and eat the Lambda's duration
+        let result =
+            match tokio::time::timeout(retry.timeout, post(&client, to, webhook, token, &p, retry))
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => {
+                    log::error!(
+                        finding_id = id;
+                        "ERR: Timed out after {:?} waiting for {} to respond to {}",
+                        retry.timeout,
+                        to,
+                        id
+                    );
+                    return Err(AppError::Timeout {
+                        id: id.to_string(),
+                        to,
+                        after: retry.timeout,
+                    });
+                }
+            };
+
+        let (reason, retry_after) = match result {
+            Ok(response) => {
+                log::info!(finding_id = id; "Message sent to {}", to);
+                return Ok(response);
+            }
+            Err(SendFailure::Retryable {
+                reason,
+                retry_after,
+            }) => (reason, retry_after),
+            Err(SendFailure::Fatal(reason)) => {
+                log::error!(finding_id = id; "ERR: {} rejected {}: {}", to, id, reason);
+                return Err(AppError::Send {
+                    id: id.to_string(),
+                    to,
+                    reason,
+                });
+            }
+        };
+
+        let delay = retry_after.unwrap_or_else(|| retry.backoff(attempt));
+
+        if attempt >= retry.max_retries || waited + delay > retry.max_wait {
+            log::error!(
+                finding_id = id;
+                "ERR: {}, giving up after {} retries",
+                reason,
+                attempt
+            );
+            return Err(AppError::Send {
+                id: id.to_string(),
+                to,
+                reason,
+            });
+        }
+
+        attempt += 1;
+        log::warn!(
+            finding_id = id;
+            "WARN: {}, retry {} of {} in {:?}",
+            reason,
+            attempt,
+            retry.max_retries,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+        waited += delay;
+    }
+}
+
+/// Why a post didn't work, and whether it's worth trying again
+enum SendFailure {
+    /// `retry_after` is from the Retry-After header, when there was one
+    Retryable {
+        reason: String,
+        retry_after: Option<Duration>,
+    },
+    Fatal(String),
+}
+
+async fn post(
+    client: &reqwest::Client,
+    to: &str,
+    webhook: &str,
+    token: Option<&str>,
+    p: &Value,
+    retry: &RetryPolicy,
+) -> Result<Value, SendFailure> {
+    let mut request = client.post(webhook).json(p);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) if e.is_builder() => {
+            return Err(SendFailure::Fatal(format!("Bad webhook URL: {}", e)))
+        }
+        // Connection problems are usually transient
+        Err(e) => {
+            return Err(SendFailure::Retryable {
+                reason: format!("HTTP send error: {}", e),
+                retry_after: None,
+            })
+        }
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        // Webhooks just say "ok", the Web API answers 200 either way and puts the outcome in JSON
+        if token.is_none() {
+            return Ok(Value::Null);
+        }
+        let body: Value = response.json().await.unwrap_or_default();
+        return match body["ok"].as_bool() {
+            Some(true) => Ok(body),
+            _ => Err(SendFailure::Fatal(format!(
+                "Slack API error: {}",
+                body["error"].as_str().unwrap_or("unknown")
+            ))),
+        };
+    }
+
+    let reason = format!("{} responded with HTTP {}", to, status);
+    // Any other 4xx is usually a payload Slack will never take, so by default it isn't retried
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+        || (retry.retry_on_4xx && status.is_client_error())
+    {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs);
+
+        Err(SendFailure::Retryable {
+            reason,
+            retry_after,
+        })
+    } else {
+        Err(SendFailure::Fatal(reason))
+    }
+}
+
+/// How hard to try when Slack is having a bad time. `max_wait` caps the total time spent
+/// sleeping between attempts, so retries can't run the Lambda into its timeout. `timeout` caps
+/// each attempt, so a webhook that never answers doesn't either. 429s and 5xx are always retried,
+/// other 4xx only with `retry_on_4xx`.
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub max_wait: Duration,
+    pub timeout: Duration,
+    pub retry_on_4xx: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            max_wait: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+            retry_on_4xx: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Configured with SLACK_MAX_RETRIES, SLACK_MAX_RETRY_WAIT_SECS, SLACK_TIMEOUT_SECS and
+    /// SLACK_RETRY_ON_4XX
+    pub(crate) fn from_env() -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: env_parse("SLACK_MAX_RETRIES", default.max_retries),
+            max_wait: Duration::from_secs(env_parse(
+                "SLACK_MAX_RETRY_WAIT_SECS",
+                default.max_wait.as_secs(),
+            )),
+            timeout: Duration::from_secs(env_parse(
+                "SLACK_TIMEOUT_SECS",
+                default.timeout.as_secs(),
+            )),
+            retry_on_4xx: env_bool("SLACK_RETRY_ON_4XX", default.retry_on_4xx),
+        }
+    }
+
+    /// 0.5s, 1s, 2s... plus up to 250ms of jitter so a burst of retries doesn't land together
+    fn backoff(&self, attempt: u32) -> Duration {
+        let jitter = rand::thread_rng().gen_range(0..250);
+        Duration::from_millis(500 * 2u64.pow(attempt.min(10)) + jitter)
+    }
+}
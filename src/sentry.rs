@@ -0,0 +1,109 @@
+//! Reports findings that couldn't be read or sent to Sentry, set with SENTRY_DSN. The event goes
+//! straight to Sentry's store endpoint, so there's no SDK to pull in, and nothing happens at all
+//! without a DSN.
+//! https://develop.sentry.dev/sdk/store/
+
+use crate::{env_bool, settings};
+use chrono::prelude::*;
+use rand::Rng;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Sentry drops whole events over its size limits, so the raw finding is cut down well short
+const MAX_RAW_CHARS: usize = 8000;
+
+pub struct Sentry {
+    store_url: String,
+    key: String,
+}
+
+impl Sentry {
+    /// None when SENTRY_DSN isn't set, or isn't a DSN, in which case it's logged and ignored
+    pub fn from_env() -> Option<Sentry> {
+        let dsn = settings::var("SENTRY_DSN")
+            .ok()
+            .filter(|dsn| !dsn.is_empty())?;
+        match Sentry::from_dsn(&dsn) {
+            Some(sentry) => Some(sentry),
+            None => {
+                log::warn!("WARN: SENTRY_DSN isn't a Sentry DSN, errors won't be reported");
+                None
+            }
+        }
+    }
+
+    /// A DSN looks like https://<key>@o0.ingest.sentry.io/<project>
+    pub fn from_dsn(dsn: &str) -> Option<Sentry> {
+        let url = Url::parse(dsn).ok()?;
+        let key = url.username();
+        let project = url.path_segments()?.next_back()?;
+        if key.is_empty() || project.is_empty() {
+            return None;
+        }
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str()?, port),
+            None => url.host_str()?.to_string(),
+        };
+        Some(Sentry {
+            store_url: format!("{}://{}/api/{}/store/", url.scheme(), host, project),
+            key: key.to_string(),
+        })
+    }
+
+    pub fn store_url(&self) -> &str {
+        &self.store_url
+    }
+
+    /// Sends the error with the finding id and raw message, when there are any. There's one try
+    /// and a failure is only logged, reporting an error mustn't cause another.
+    pub async fn capture(
+        &self,
+        error: &str,
+        finding_id: Option<&str>,
+        raw: Option<&str>,
+        timeout: Duration,
+    ) {
+        if env_bool("DRY_RUN", false) {
+            log::info!("DRY_RUN, not reporting to Sentry: {}", error);
+            return;
+        }
+
+        let event = event(error, finding_id, raw);
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_key={}, sentry_client=guarddutyslack/{}",
+            self.key,
+            env!("CARGO_PKG_VERSION")
+        );
+        let result = reqwest::Client::new()
+            .post(&self.store_url)
+            .header("X-Sentry-Auth", auth)
+            .timeout(timeout)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        if let Err(e) = result {
+            log::warn!("WARN: Couldn't report the error to Sentry: {}", e);
+        }
+    }
+}
+
+/// The Sentry event for an error, tagged with the finding id so events can be searched by it
+pub fn event(error: &str, finding_id: Option<&str>, raw: Option<&str>) -> Value {
+    let event_id = format!("{:032x}", rand::thread_rng().gen::<u128>());
+    let raw = raw.map(|raw| raw.chars().take(MAX_RAW_CHARS).collect::<String>());
+
+    json!({
+        "event_id": event_id,
+        "timestamp": Utc::now().to_rfc3339(),
+        "platform": "other",
+        "level": "error",
+        "logger": "guarddutyslack",
+        "message": { "formatted": error },
+        "tags": { "finding_id": finding_id },
+        "extra": { "raw_message": raw },
+    })
+}
@@ -0,0 +1,100 @@
+//! Where settings are read from. Each one is an environment variable, or failing that a key of
+//! the same name in CONFIG_JSON, so the whole config can live in one variable (or one SSM
+//! parameter) and single settings can still be overridden on their own.
+
+use crate::AppError;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::env::{self, VarError};
+use std::sync::{Mutex, OnceLock};
+
+static CONFIG_JSON: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Every setting that's been asked for, to tell a misspelled key in CONFIG_JSON from a real one
+static READ: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Settings read each time they're needed rather than with the rest at startup
+const READ_LATER: &[&str] = &["DRY_RUN"];
+
+/// Reads CONFIG_JSON, once at startup before anything else is read. Bad JSON stops the Lambda
+/// starting, the same as a missing webhook would.
+pub fn load() -> Result<(), AppError> {
+    let settings = match env::var("CONFIG_JSON") {
+        Ok(raw) => parse(&raw).map_err(AppError::Config)?,
+        Err(_) => HashMap::new(),
+    };
+    CONFIG_JSON.get_or_init(|| settings);
+    Ok(())
+}
+
+/// Turns CONFIG_JSON into what the environment variables would have held. Strings are kept as
+/// they are, lists are joined with commas (for SUPPRESS_TYPES) and objects are left as JSON
+/// (for ACCOUNT_NAME_MAP), so every setting is parsed the one way whichever it came from.
+pub fn parse(raw: &str) -> Result<HashMap<String, String>, String> {
+    let object = match serde_json::from_str(raw) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => return Err(String::from("should be a JSON object")),
+        Err(e) => return Err(format!("not valid JSON ({})", e)),
+    };
+
+    let mut settings = HashMap::new();
+    for (key, value) in object {
+        let value = match value {
+            Value::Null => continue,
+            Value::String(s) => s,
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        };
+        settings.insert(key, value);
+    }
+    Ok(settings)
+}
+
+/// Like `env::var`, with CONFIG_JSON as the fallback. The environment always wins.
+pub fn var(key: &str) -> Result<String, VarError> {
+    READ.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key.to_string());
+    match env::var(key) {
+        Err(VarError::NotPresent) => CONFIG_JSON
+            .get()
+            .and_then(|settings| settings.get(key))
+            .cloned()
+            .ok_or(VarError::NotPresent),
+        result => result,
+    }
+}
+
+/// The keys in `settings` nothing has asked for, in order. Once the config's been read these are
+/// typos, or settings that do nothing without another, like THREAD_TABLE without SLACK_BOT_TOKEN.
+pub fn unread(settings: &HashMap<String, String>) -> Vec<&str> {
+    let read = READ.lock().unwrap_or_else(|e| e.into_inner());
+    let mut keys: Vec<&str> = settings
+        .keys()
+        .map(String::as_str)
+        .filter(|key| !read.contains(*key) && !READ_LATER.contains(key))
+        .collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// Warns about each key in CONFIG_JSON the config didn't use, so a typo doesn't go unnoticed
+pub fn warn_unread() {
+    let settings = match CONFIG_JSON.get() {
+        Some(settings) => settings,
+        None => return,
+    };
+    for key in unread(settings) {
+        log::warn!(
+            "WARN: CONFIG_JSON has {}, which isn't a setting or does nothing with the others set",
+            key
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Microsoft Teams incoming webhooks, for when DESTINATION=teams. These take the legacy
+//! MessageCard format, which is still what Teams webhooks (and Power Automate workflows
+//! replacing them) accept without any extra setup.
+//! https://learn.microsoft.com/en-us/outlook/actionable-messages/message-card-reference
+
+use crate::{Config, Message};
+use serde_json::{json, Value};
+
+/// One card for every finding given, each finding is its own section. Teams has no equivalent of
+/// @channel in a webhook message, so mentions are left off.
+pub fn card(messages: &[Message], config: &Config) -> Value {
+    let summary = match messages {
+        [message] => format!("GuardDuty:{}", message.detail.tipe),
+        _ => format!("{} GuardDuty findings", messages.len()),
+    };
+
+    // Teams only has the one colour strip per card, so the first (most severe) finding sets it
+    let theme_colour = messages
+        .first()
+        .map(|m| m.level(config).colour.trim_start_matches('#').to_string())
+        .unwrap_or_default();
+
+    json!({
+        "@type": "MessageCard",
+        "@context": "https://schema.org/extensions",
+        "summary": summary,
+        "themeColor": theme_colour,
+        "sections": messages.iter().map(|m| section(m, config)).collect::<Vec<_>>(),
+    })
+}
+
+fn section(message: &Message, config: &Config) -> Value {
+    let facts = message
+        .summary_fields(config)
+        .into_iter()
+        .chain(message.resource_fields())
+        .chain(message.resources_field())
+        .chain(message.tag_fields(&config.tag_keys))
+        .chain(message.scan_fields())
+        .chain(message.action_fields(config))
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "activityTitle": format!("[{}]({})", message.title(config), message.title_link(config)),
+        "activitySubtitle": format!(
+            "{} in {} from account {}",
+            message.status_label(),
+            message.region_label(&config.region_names),
+            message.account_label(&config.account_names)
+        ),
+        "text": match message.api_caller() {
+            Some(caller) => format!("**{}**\n\n{}", caller, message.description()),
+            None => message.description(),
+        },
+        "facts": facts,
+        "potentialAction": [{
+            "@type": "OpenUri",
+            "name": "View finding",
+            "targets": [{ "os": "default", "uri": message.console_link() }],
+        }],
+    })
+}
+
+impl Message {
+    /// Builds a Microsoft Teams MessageCard for the finding, for when DESTINATION=teams
+    pub fn build_teams_card(&self, config: &Config) -> Value {
+        card(std::slice::from_ref(self), config)
+    }
+}
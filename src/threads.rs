@@ -0,0 +1,151 @@
+//! Posting with a Slack app's bot token instead of a webhook, set with SLACK_BOT_TOKEN and
+//! SLACK_CHANNEL. Unlike a webhook, chat.postMessage says where the message landed, so when
+//! THREAD_TABLE names a DynamoDB table, updates to a finding are posted as replies in its thread.
+//! https://api.slack.com/methods/chat.postMessage
+
+use crate::send::{send, Poster, Posting};
+use crate::{settings, Message, RetryPolicy};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use chrono::prelude::*;
+use serde_json::Value;
+
+pub const POST_MESSAGE_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// GuardDuty keeps findings for 90 days, there's no point remembering a thread for longer
+const THREAD_TTL_SECS: i64 = 90 * 24 * 60 * 60;
+
+pub struct SlackBot {
+    pub token: String,
+    channel: String,
+    threads: Option<ThreadStore>,
+}
+
+struct ThreadStore {
+    client: Client,
+    table: String,
+}
+
+impl SlackBot {
+    /// None unless both SLACK_BOT_TOKEN and SLACK_CHANNEL are set, then webhooks aren't used
+    pub async fn from_env() -> Option<SlackBot> {
+        let token = settings::var("SLACK_BOT_TOKEN").ok()?;
+        let channel = match settings::var("SLACK_CHANNEL") {
+            Ok(channel) => channel,
+            Err(_) => {
+                log::warn!("WARN: SLACK_BOT_TOKEN is set without SLACK_CHANNEL, using webhooks");
+                return None;
+            }
+        };
+
+        let threads = match settings::var("THREAD_TABLE") {
+            Ok(table) => Some(ThreadStore {
+                client: Client::new(&aws_config::load_from_env().await),
+                table,
+            }),
+            Err(_) => None,
+        };
+
+        Some(SlackBot {
+            token,
+            channel,
+            threads,
+        })
+    }
+
+    /// The webhook payload plus where chat.postMessage should put it. A channel already in the
+    /// payload, from SLACK_CHANNEL_CRITICAL and friends, wins over SLACK_CHANNEL.
+    pub fn body(&self, mut payload: Value, thread_ts: Option<&str>) -> Value {
+        if payload.get("channel").is_none() {
+            payload["channel"] = Value::from(&*self.channel);
+        }
+        if let Some(ts) = thread_ts {
+            payload["thread_ts"] = Value::from(ts);
+        }
+        payload
+    }
+
+    /// The `ts` of the first message sent for this finding, if there was one
+    pub async fn thread_for(&self, message: &Message) -> Option<String> {
+        let store = self.threads.as_ref()?;
+        let result = store
+            .client
+            .get_item()
+            .table_name(&store.table)
+            .key("id", AttributeValue::S(key(message)))
+            .send()
+            .await;
+
+        match result {
+            Ok(output) => output
+                .item()
+                .and_then(|item| item.get("ts"))
+                .and_then(|ts| ts.as_s().ok())
+                .cloned(),
+            Err(e) => {
+                log::warn!(
+                    finding_id = message.detail.id.as_str();
+                    "WARN: Couldn't look up the thread for {}, posting a new message: {}",
+                    message.detail.id,
+                    e.into_service_error()
+                );
+                None
+            }
+        }
+    }
+
+    /// Keeps the `ts` from Slack's response so later updates can reply to it
+    pub async fn remember(&self, message: &Message, response: &Value) {
+        let (store, ts) = match (&self.threads, response["ts"].as_str()) {
+            (Some(store), Some(ts)) => (store, ts),
+            _ => return,
+        };
+
+        let expires_at = Utc::now().timestamp() + THREAD_TTL_SECS;
+        let result = store
+            .client
+            .put_item()
+            .table_name(&store.table)
+            .item("id", AttributeValue::S(key(message)))
+            .item("ts", AttributeValue::S(ts.to_string()))
+            .item("expires_at", AttributeValue::N(expires_at.to_string()))
+            .send()
+            .await;
+
+        if let Err(e) = result {
+            log::warn!(
+                finding_id = message.detail.id.as_str();
+                "WARN: Couldn't save the thread for {}, updates will be new messages: {}",
+                message.detail.id,
+                e.into_service_error()
+            );
+        }
+    }
+}
+
+/// Everything goes to chat.postMessage, the channel saying where rather than the webhook
+impl Poster for SlackBot {
+    fn post<'a>(
+        &'a self,
+        id: &'a str,
+        _webhook: &'a str,
+        body: Value,
+        thread: Option<&'a str>,
+        retry: &'a RetryPolicy,
+    ) -> Posting<'a> {
+        let body = self.body(body, thread);
+        Box::pin(send(
+            id,
+            "Slack",
+            POST_MESSAGE_URL,
+            Some(&self.token),
+            body,
+            retry,
+        ))
+    }
+}
+
+/// Prefixed so the dedupe table can double as the thread table
+fn key(message: &Message) -> String {
+    format!("thread#{}", message.detail.id)
+}
@@ -0,0 +1,133 @@
+//! Optional posting of only the findings that are new or have changed severity band, set with
+//! NOTIFY_ON_TRANSITION_ONLY. The last band posted for each finding is kept in the DynamoDB table
+//! named in TRANSITION_TABLE, which takes the same shape of table as DEDUPE_TABLE and can be the
+//! same table. Without one it's kept by the container, and forgotten when the container is.
+
+use crate::{env_bool, settings, Message};
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+use aws_sdk_dynamodb::Client;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// GuardDuty keeps findings for 90 days, there's no point remembering a band for longer
+const TRANSITION_TTL_SECS: i64 = 90 * 24 * 60 * 60;
+
+pub struct Transitions {
+    store: Store,
+}
+
+enum Store {
+    Memory(Mutex<HashMap<String, String>>),
+    Table { client: Client, table: String },
+}
+
+impl Transitions {
+    /// Bands are only remembered as long as this lasts, so a cold start posts everything again
+    pub fn in_memory() -> Transitions {
+        Transitions {
+            store: Store::Memory(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// None unless NOTIFY_ON_TRANSITION_ONLY is on, then TRANSITION_TABLE if it's set
+    pub async fn from_env() -> Option<Transitions> {
+        if !env_bool("NOTIFY_ON_TRANSITION_ONLY", false) {
+            return None;
+        }
+
+        match settings::var("TRANSITION_TABLE") {
+            Ok(table) => Some(Transitions {
+                store: Store::Table {
+                    client: Client::new(&aws_config::load_from_env().await),
+                    table,
+                },
+            }),
+            Err(_) => {
+                log::warn!(
+                    "WARN: NOTIFY_ON_TRANSITION_ONLY is set without TRANSITION_TABLE, bands are \
+                     only remembered until the container is recycled"
+                );
+                Some(Transitions::in_memory())
+            }
+        }
+    }
+
+    /// Records `band` as the finding's band, true if it's a finding not seen before or it was in
+    /// another band last time. If DynamoDB can't be reached the finding is let through, a repeat
+    /// post beats a missed escalation.
+    pub async fn is_transition(&self, message: &Message, band: &str) -> bool {
+        let previous = match &self.store {
+            Store::Memory(bands) => bands
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(key(message), band.to_string()),
+            Store::Table { client, table } => {
+                let expires_at = Utc::now().timestamp() + TRANSITION_TTL_SECS;
+                let result = client
+                    .put_item()
+                    .table_name(table)
+                    .item("id", AttributeValue::S(key(message)))
+                    .item("band", AttributeValue::S(band.to_string()))
+                    .item("expires_at", AttributeValue::N(expires_at.to_string()))
+                    .return_values(ReturnValue::AllOld)
+                    .send()
+                    .await;
+
+                match result {
+                    Ok(output) => output
+                        .attributes()
+                        .and_then(|item| item.get("band"))
+                        .and_then(|band| band.as_s().ok())
+                        .cloned(),
+                    Err(e) => {
+                        log::warn!(
+                            finding_id = message.detail.id.as_str();
+                            "WARN: Couldn't check {} for a band change, sending anyway: {}",
+                            table,
+                            e.into_service_error()
+                        );
+                        None
+                    }
+                }
+            }
+        };
+
+        previous.as_deref() != Some(band)
+    }
+
+    /// Forgets a finding that failed to send, so the next delivery of it is posted
+    pub async fn forget(&self, message: &Message) {
+        match &self.store {
+            Store::Memory(bands) => {
+                bands
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&key(message));
+            }
+            Store::Table { client, table } => {
+                let result = client
+                    .delete_item()
+                    .table_name(table)
+                    .key("id", AttributeValue::S(key(message)))
+                    .send()
+                    .await;
+
+                if let Err(e) = result {
+                    log::warn!(
+                        finding_id = message.detail.id.as_str();
+                        "WARN: Couldn't remove {} from {}, a retry may be skipped: {}",
+                        message.detail.id,
+                        table,
+                        e.into_service_error()
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Prefixed so the dedupe table can double as the transition table
+fn key(message: &Message) -> String {
+    format!("band#{}", message.detail.id)
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::{config_from_env, fixture, fixture_json};
+use guarddutyslack::{build_batch_payload, group_by_type, Config, Message};
+use serde_json::{json, Value};
+
+/// The ec2.json finding about a different instance
+fn ec2_on(instance_id: &str) -> Message {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["resource"]["instanceDetails"]["instanceId"] = json!(instance_id);
+    serde_json::from_value(json).unwrap()
+}
+
+fn batch_json(messages: &[Message]) -> Value {
+    serde_json::to_value(build_batch_payload(messages, &Config::default()).unwrap()).unwrap()
+}
+
+#[test]
+fn same_type_findings_are_grouped() {
+    let messages = vec![
+        ec2_on("i-11111111"),
+        ec2_on("i-22222222"),
+        ec2_on("i-33333333"),
+    ];
+
+    let groups = group_by_type(&messages);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].len(), 3);
+}
+
+#[test]
+fn mixed_types_keep_the_order_they_turn_up_in() {
+    let messages = vec![
+        ec2_on("i-11111111"),
+        fixture("s3.json"),
+        ec2_on("i-22222222"),
+    ];
+
+    let groups = group_by_type(&messages);
+    let types: Vec<(&str, usize)> = groups
+        .iter()
+        .map(|group| (group[0].detail.tipe.as_str(), group.len()))
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            ("UnauthorizedAccess:EC2/MetadataDNSRebind", 2),
+            ("Policy:S3/BucketBlockPublicAccessDisabled", 1),
+        ]
+    );
+}
+
+#[test]
+fn group_shares_one_attachment_with_a_count() {
+    let messages = vec![
+        ec2_on("i-11111111"),
+        ec2_on("i-22222222"),
+        ec2_on("i-33333333"),
+    ];
+    let json = batch_json(&messages);
+
+    let attachments = json["attachments"].as_array().unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(
+        attachments[0]["pretext"],
+        "*3 UnauthorizedAccess:EC2/MetadataDNSRebind findings in this delivery* @channel"
+    );
+
+    let affected = attachments[0]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["title"] == "Affected resources")
+        .unwrap()["value"]
+        .as_str()
+        .unwrap();
+    assert_eq!(
+        affected,
+        "Instance i-11111111 in 999999999999 eu-west-2\nInstance i-22222222 in 999999999999 eu-west-2\nInstance i-33333333 in 999999999999 eu-west-2"
+    );
+}
+
+#[test]
+fn lone_findings_in_a_batch_keep_their_own_attachment() {
+    let messages = vec![
+        ec2_on("i-11111111"),
+        ec2_on("i-22222222"),
+        fixture("s3.json"),
+    ];
+    let json = batch_json(&messages);
+
+    let attachments = json["attachments"].as_array().unwrap();
+    assert_eq!(attachments.len(), 2);
+    assert!(attachments[0]["pretext"]
+        .as_str()
+        .unwrap()
+        .starts_with("*2 UnauthorizedAccess:EC2/MetadataDNSRebind findings"));
+    // Only the first attachment of a batch mentions anyone
+    assert_eq!(
+        attachments[1]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+    assert_eq!(
+        attachments[1]["title"],
+        "Policy:S3/BucketBlockPublicAccessDisabled"
+    );
+}
+
+/// `name` with its severity changed
+fn with_severity(name: &str, severity: f32) -> Message {
+    let mut json = fixture_json(name);
+    json["detail"]["severity"] = json!(severity);
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn two_critical_findings_ping_once() {
+    let messages = vec![
+        with_severity("ec2.json", 9.5),
+        with_severity("s3.json", 9.5),
+    ];
+    let json = batch_json(&messages);
+
+    assert_eq!(json["attachments"].as_array().unwrap().len(), 2);
+    assert_eq!(json.to_string().matches("@channel").count(), 1);
+    assert!(json["attachments"][0]["pretext"]
+        .as_str()
+        .unwrap()
+        .ends_with("@channel"));
+}
+
+#[test]
+fn mention_goes_on_the_most_severe_finding() {
+    let messages = vec![
+        with_severity("s3.json", 5.0),
+        with_severity("ec2.json", 9.5),
+    ];
+    let json = batch_json(&messages);
+
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+    assert_eq!(
+        json["attachments"][1]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* @channel"
+    );
+}
+
+#[test]
+fn mention_skips_a_band_with_none() {
+    let config = config_from_env(&[
+        (
+            "WEBHOOK_URL",
+            "https://hooks.slack.com/services/T000/B000/XXXX",
+        ),
+        ("MENTION_CRITICAL", ""),
+        ("MENTION_MEDIUM", "@oncall"),
+    ])
+    .unwrap();
+    let messages = vec![
+        with_severity("ec2.json", 9.5),
+        with_severity("s3.json", 5.0),
+    ];
+    let json = serde_json::to_value(build_batch_payload(&messages, &config).unwrap()).unwrap();
+
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+    assert_eq!(
+        json["attachments"][1]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* @oncall"
+    );
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::Config;
+use serde_json::json;
+
+#[test]
+fn block_kit_message() {
+    let blocks = fixture("ec2.json").build_blocks(&Config::default());
+
+    let field = |title: &str, value: &str| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", title, value) });
+    assert_eq!(
+        blocks,
+        json!({
+            "text": "GuardDuty:UnauthorizedAccess:EC2/MetadataDNSRebind",
+            "link_names": true,
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": {
+                        "type": "plain_text",
+                        "text": "(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind",
+                        "emoji": true,
+                    },
+                },
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": "*New finding in eu-west-2 from account 999999999999* @channel\n<https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#unauthorizedaccess-ec2-metadatadnsrebind|Finding type docs>",
+                    },
+                },
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": "EC2 instance i-99999999 is performing DNS lookups that may indicate that it is a target of a DNS rebinding attack.",
+                    },
+                },
+                {
+                    "type": "section",
+                    "fields": [
+                        field("Finding type", "UnauthorizedAccess:EC2/MetadataDNSRebind"),
+                        field("Severity", "8 (High)"),
+                        field("Level", "High"),
+                        field("First seen", "Wed Dec 22 11:27:25 UTC"),
+                        field("Count", "1"),
+                        field("Last seen", "Wed Dec 22 11:27:25 UTC"),
+                        field("Detection lag", "0s"),
+                        field("Threat list", "GeneratedFindingThreatListName"),
+                        field("Console", "<https://eu-west-2.console.aws.amazon.com/guardduty/home?region=eu-west-2#/findings?fId=96bef0f2aff363c0dd1466bb22a7e32f|View finding>"),
+                        field("Resource type", "Instance"),
+                    ],
+                },
+                {
+                    "type": "section",
+                    "fields": [
+                        field("Instance", "i-99999999"),
+                        field("Action", "DNS_REQUEST"),
+                        field("Domain", "GeneratedFindingDomainName"),
+                        field("Blocked", "true"),
+                    ],
+                },
+                {
+                    "type": "context",
+                    "elements": [
+                        {
+                            "type": "image",
+                            "image_url": "https://rustacean.net/assets/rustacean-flat-happy.png",
+                            "alt_text": "icon",
+                        },
+                        { "type": "mrkdwn", "text": "GuardyBot | Wed Dec 22 11:27:25 UTC" },
+                    ],
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn description_is_escaped() {
+    let mut message = fixture("ec2.json");
+    message.detail.description = String::from("<!channel> & friends");
+
+    let blocks = message.build_blocks(&Config::default());
+    assert_eq!(
+        blocks["blocks"][2]["text"]["text"],
+        "&lt;!channel&gt; &amp; friends"
+    );
+}
+
+#[test]
+fn ids_are_a_context_line() {
+    let mut config = Config::default();
+    config.include_ids = true;
+
+    let blocks = fixture("ec2.json").build_blocks(&config);
+    let blocks = blocks["blocks"].as_array().unwrap();
+    // Just ahead of the footer
+    assert_eq!(
+        blocks[blocks.len() - 2]["elements"][0]["text"],
+        "Detector ID: `5cbedede2b0b34589ddf6c1149245844` | Finding ARN: `arn:aws:guardduty:eu-west-2:999999999999:detector/5cbedede2b0b34589ddf6c1149245844/finding/96bef0f2aff363c0dd1466bb22a7e32f`"
+    );
+}
+
+#[test]
+fn escaped_text_fits_in_its_block() {
+    let mut message = fixture("ec2.json");
+    message.detail.description = "<&".repeat(1500);
+    message.resources = (0..100)
+        .map(|i| format!("arn:aws:ec2:eu-west-2:999999999999:instance/i-{:08}", i))
+        .collect();
+    let mut config = Config::default();
+    config.verbose = true;
+
+    let payload = message.build_blocks(&config);
+    for block in payload["blocks"].as_array().unwrap() {
+        if let Some(text) = block["text"]["text"].as_str() {
+            assert!(text.chars().count() <= 3000, "{}", text.len());
+            assert!(!text.ends_with('&'), "{}", text);
+        }
+        for field in block["fields"].as_array().into_iter().flatten() {
+            let text = field["text"].as_str().unwrap();
+            assert!(text.chars().count() <= 2000, "{}", text.len());
+        }
+    }
+
+    let raw = payload["blocks"].as_array().unwrap().last().unwrap()["text"]["text"]
+        .as_str()
+        .unwrap();
+    assert!(raw.starts_with("```") && raw.ends_with("```"), "{}", raw);
+}
+
+#[test]
+fn extra_fields_are_escaped_like_the_attachments() {
+    let mut config = Config::default();
+    config.extra_fields = vec![(String::from("Owner"), String::from("<!channel> {region}"))];
+    let message = fixture("ec2.json");
+
+    let blocks = message.build_blocks(&config).to_string();
+    assert!(
+        blocks.contains("*Owner*\\n&lt;!channel&gt; eu-west-2"),
+        "{}",
+        blocks
+    );
+    assert!(!blocks.contains("<!channel>"), "{}", blocks);
+
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    let fields = json["attachments"][0]["fields"].as_array().unwrap();
+    let owner = fields.iter().find(|f| f["title"] == "Owner").unwrap();
+    assert_eq!(owner["value"], "&lt;!channel&gt; eu-west-2");
+}
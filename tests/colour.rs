@@ -0,0 +1,57 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::{hex_colour, Config};
+
+#[test]
+fn valid_colour_override() {
+    assert_eq!(hex_colour("#FF0000"), Some(String::from("#FF0000")));
+    assert_eq!(hex_colour("#1a2b3c"), Some(String::from("#1A2B3C")));
+    assert_eq!(hex_colour("1a2b3c"), Some(String::from("#1A2B3C")));
+}
+
+#[test]
+fn malformed_colour_is_rejected() {
+    assert_eq!(hex_colour("red"), None);
+    assert_eq!(hex_colour("#FFF"), None);
+    assert_eq!(hex_colour("#GG0000"), None);
+    assert_eq!(hex_colour(""), None);
+}
+
+/// The colour and mention each band gets with the default config. Severities either side of each
+/// threshold, plus the ends of GuardDuty's 1.0 to 10.0 range.
+#[test]
+fn severity_bands() {
+    let cases = [
+        (10.0, "#DF4661", "@channel"), // Critical, red
+        (9.5, "#DF4661", "@channel"),
+        (9.0, "#DF4661", "@channel"),
+        (8.9, "#DB6B30", "@channel"), // High, orange
+        (7.0, "#DB6B30", "@channel"),
+        (6.9, "#FED141", "@here"), // Medium, yellow
+        (4.0, "#FED141", "@here"),
+        (3.9, "#00A3E0", ""), // Low, blue
+        (2.0, "#00A3E0", ""),
+        (1.0, "#00A3E0", ""),
+        (0.5, "#BABABA", ""), // Unknown, silver
+    ];
+
+    let config = Config::default();
+    for (severity, colour, mention) in &cases {
+        let mut message = fixture("ec2.json");
+        message.detail.severity = *severity;
+        let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+        let attachment = &json["attachments"][0];
+
+        assert_eq!(attachment["color"], *colour, "severity {}", severity);
+        assert_eq!(
+            attachment["pretext"],
+            format!(
+                "*New finding in eu-west-2 from account 999999999999* {}",
+                mention
+            ),
+            "severity {}",
+            severity
+        );
+    }
+}
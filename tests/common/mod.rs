@@ -0,0 +1,138 @@
+use guarddutyslack::{AppError, Config, Message};
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Loads a finding from tests/fixtures, these are the EventBridge events GuardDuty emits for its
+/// sample findings
+#[allow(dead_code)]
+pub fn fixture(name: &str) -> Message {
+    serde_json::from_value(fixture_json(name)).expect("Fixture didn't deserialize into a Message")
+}
+
+/// The raw fixture, for tests that need to mangle it before it's deserialized
+#[allow(dead_code)]
+pub fn fixture_json(name: &str) -> Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let raw = fs::read_to_string(&path).expect("Fixture missing");
+    serde_json::from_str(&raw).expect("Fixture isn't valid JSON")
+}
+
+/// `Config::from_env` with just `vars` set. The environment is shared by every test in the
+/// binary, so they take turns, and each one's settings are cleared after it.
+#[allow(dead_code)]
+pub fn config_from_env(vars: &[(&str, &str)]) -> Result<Config, AppError> {
+    static ENV: Mutex<()> = Mutex::new(());
+    let _turn = ENV.lock().unwrap_or_else(|e| e.into_inner());
+
+    for (key, value) in vars {
+        env::set_var(key, value);
+    }
+    let config = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(Config::from_env());
+    for (key, _) in vars {
+        env::remove_var(key);
+    }
+    config
+}
+
+/// An SNS notification record, as SNS delivers it
+#[allow(dead_code)]
+pub fn sns_record(message: Option<String>) -> Value {
+    json!({
+        "EventSource": "aws:sns",
+        "EventVersion": "1.0",
+        "EventSubscriptionArn": "arn:aws:sns:eu-west-2:999999999999:guardduty-findings:8008b663",
+        "Sns": {
+            "Type": "Notification",
+            "MessageId": "db0647ae-0666-5cce-ae11-1d9308d3f24e",
+            "TopicArn": "arn:aws:sns:eu-west-2:999999999999:guardduty-findings",
+            "Timestamp": "2021-12-22T11:31:01.645Z",
+            "Message": message,
+        },
+    })
+}
+
+/// A stand-in for a Slack webhook on localhost. Posts get the `statuses` back in turn, the last
+/// one over and over, after `delay`. The bodies posted are kept in `requests` and `answered`
+/// counts the responses sent.
+#[allow(dead_code)]
+pub struct MockSlack {
+    pub url: String,
+    pub requests: Arc<Mutex<Vec<String>>>,
+    pub answered: Arc<AtomicUsize>,
+}
+
+#[allow(dead_code)]
+pub fn mock_slack(statuses: &[u16], delay: Duration) -> MockSlack {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't start the mock Slack");
+    let url = format!(
+        "http://{}/services/T000/B000/XXXX",
+        listener.local_addr().unwrap()
+    );
+    let requests = Arc::new(Mutex::new(vec![]));
+    let answered = Arc::new(AtomicUsize::new(0));
+
+    let statuses = statuses.to_vec();
+    let (received, responses) = (requests.clone(), answered.clone());
+    thread::spawn(move || {
+        for (i, stream) in listener.incoming().enumerate() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+            let body = read_body(&mut stream);
+            received.lock().unwrap().push(body);
+
+            thread::sleep(delay);
+            // Counted before it's written, so the sender can't see a response that isn't counted
+            responses.fetch_add(1, Ordering::SeqCst);
+            let status = statuses[i.min(statuses.len() - 1)];
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                status
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    MockSlack {
+        url,
+        requests,
+        answered,
+    }
+}
+
+/// Reads one HTTP request off the stream, all that's kept is the body
+fn read_body(stream: &mut TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; length];
+    let _ = reader.read_exact(&mut body);
+    String::from_utf8_lossy(&body).into_owned()
+}
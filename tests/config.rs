@@ -0,0 +1,179 @@
+mod common;
+
+use common::{config_from_env, fixture};
+use guarddutyslack::{AppError, Config, Skip};
+
+const WEBHOOK: &str = "https://hooks.slack.com/services/T000/B000/XXXX";
+
+#[test]
+fn bot_token_rejects_webhook_routing() {
+    for var in &["WEBHOOK_URL_HIGH", "ACCOUNT_WEBHOOK_MAP"] {
+        let value = match *var {
+            "ACCOUNT_WEBHOOK_MAP" => format!(r#"{{"123456789012":"{}"}}"#, WEBHOOK),
+            _ => WEBHOOK.to_string(),
+        };
+        let result = config_from_env(&[
+            ("SLACK_BOT_TOKEN", "xoxb-0000"),
+            ("SLACK_CHANNEL", "C0000"),
+            (var, &value),
+        ]);
+
+        match result {
+            Err(AppError::InvalidWebhook { var: rejected, .. }) => assert_eq!(rejected, *var),
+            other => panic!("{} should be rejected, got {:?}", var, other.err()),
+        }
+    }
+}
+
+#[test]
+fn bot_token_needs_no_webhook() {
+    let config = config_from_env(&[("SLACK_BOT_TOKEN", "xoxb-0000"), ("SLACK_CHANNEL", "C0000")]);
+    assert!(config.is_ok());
+}
+
+/// The colour the finding's attachment gets at `severity`
+fn colour(config: &Config, severity: f32) -> String {
+    let mut message = fixture("ec2.json");
+    message.detail.severity = severity;
+    let json = serde_json::to_value(message.build_payload(config).unwrap()).unwrap();
+    json["attachments"][0]["color"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn severity_thresholds_can_be_moved() {
+    let config = config_from_env(&[
+        ("WEBHOOK_URL", WEBHOOK),
+        ("SEVERITY_CRITICAL_MIN", "8.0"),
+        ("SEVERITY_HIGH_MIN", "6.0"),
+    ])
+    .unwrap();
+
+    assert_eq!(colour(&config, 8.0), "#DF4661"); // Critical
+    assert_eq!(colour(&config, 7.9), "#DB6B30"); // High
+    assert_eq!(colour(&config, 6.0), "#DB6B30");
+    assert_eq!(colour(&config, 5.9), "#FED141"); // Medium
+}
+
+#[test]
+fn unusable_thresholds_keep_the_defaults() {
+    let config = config_from_env(&[
+        ("WEBHOOK_URL", WEBHOOK),
+        ("SEVERITY_CRITICAL_MIN", "very"),
+        ("SEVERITY_HIGH_MIN", "11.0"),
+        ("SEVERITY_MEDIUM_MIN", "-1"),
+    ])
+    .unwrap();
+
+    assert_eq!(colour(&config, 9.0), "#DF4661"); // Critical
+    assert_eq!(colour(&config, 8.9), "#DB6B30"); // High
+    assert_eq!(colour(&config, 7.0), "#DB6B30");
+    assert_eq!(colour(&config, 6.9), "#FED141"); // Medium
+    assert_eq!(colour(&config, 4.0), "#FED141");
+    assert_eq!(colour(&config, 3.9), "#00A3E0"); // Low
+}
+
+#[test]
+fn findings_go_to_their_bands_webhook() {
+    let high = "https://hooks.slack.com/services/T000/B000/HIGH";
+    let config = config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("WEBHOOK_URL_HIGH", high)]).unwrap();
+
+    let mut message = fixture("ec2.json");
+    message.detail.severity = 8.5;
+    assert_eq!(config.webhook_for(&message), high);
+}
+
+#[test]
+fn bands_without_a_webhook_use_the_default() {
+    let high = "https://hooks.slack.com/services/T000/B000/HIGH";
+    let config = config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("WEBHOOK_URL_HIGH", high)]).unwrap();
+
+    let mut message = fixture("ec2.json");
+    for severity in &[9.5, 5.0, 2.0] {
+        message.detail.severity = *severity;
+        assert_eq!(
+            config.webhook_for(&message),
+            WEBHOOK,
+            "severity {}",
+            severity
+        );
+    }
+}
+
+/// The First seen field when the activity was first seen at `time`
+fn first_seen(config: &Config, time: &str) -> String {
+    let mut message = fixture("ec2.json");
+    message.detail.service.event_first_seen = time.parse().unwrap();
+    let json = serde_json::to_value(message.build_payload(config).unwrap()).unwrap();
+    let fields = json["attachments"][0]["fields"].as_array().unwrap().clone();
+    let field = fields.iter().find(|f| f["title"] == "First seen").unwrap();
+    field["value"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn times_are_shown_in_display_tz() {
+    let config =
+        config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("DISPLAY_TZ", "Europe/London")]).unwrap();
+
+    assert_eq!(
+        first_seen(&config, "2021-12-22T11:27:25Z"),
+        "Wed Dec 22 11:27:25 GMT"
+    );
+    // British Summer Time is an hour ahead of UTC
+    assert_eq!(
+        first_seen(&config, "2021-07-01T11:27:25Z"),
+        "Thu Jul  1 12:27:25 BST"
+    );
+}
+
+#[test]
+fn unknown_display_tz_is_utc() {
+    let config =
+        config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("DISPLAY_TZ", "Europe/Atlantis")]).unwrap();
+
+    assert_eq!(
+        first_seen(&config, "2021-07-01T11:27:25Z"),
+        "Thu Jul  1 11:27:25 UTC"
+    );
+}
+
+#[test]
+fn archived_findings_can_be_suppressed() {
+    let config =
+        config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("SUPPRESS_ARCHIVED", "true")]).unwrap();
+
+    let mut message = fixture("ec2.json");
+    assert_eq!(message.skip_reason(&config), None);
+    message.detail.service.archived = true;
+    assert_eq!(message.skip_reason(&config), Some(Skip::Archived));
+}
+
+#[test]
+fn archived_findings_are_greyed_out_without_a_mention() {
+    let config = config_from_env(&[("WEBHOOK_URL", WEBHOOK), ("MENTION_HIGH", "@here")]).unwrap();
+
+    let mut message = fixture("ec2.json");
+    message.detail.service.archived = true;
+    assert_eq!(message.skip_reason(&config), None);
+
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    assert_eq!(json["attachments"][0]["color"], "#BABABA");
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+
+    // Still High, so it would have been
+    message.detail.service.archived = false;
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    assert!(
+        json["attachments"][0]["pretext"]
+            .as_str()
+            .unwrap()
+            .ends_with("@here"),
+        "{}",
+        json["attachments"][0]["pretext"]
+    );
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::{fixture, fixture_json};
+use guarddutyslack::{Message, RecentFindings};
+
+#[test]
+fn replay_is_caught() {
+    let recent = RecentFindings::new(10);
+    let message = fixture("ec2.json");
+
+    assert!(!recent.already_seen(&message));
+    assert!(recent.already_seen(&message));
+}
+
+#[test]
+fn update_to_a_finding_is_not_a_replay() {
+    let recent = RecentFindings::new(10);
+    let mut json = fixture_json("ec2.json");
+    let original: Message = serde_json::from_value(json.clone()).unwrap();
+    json["detail"]["updatedAt"] = "2030-01-01T00:00:00Z".into();
+    let update: Message = serde_json::from_value(json).unwrap();
+
+    assert!(!recent.already_seen(&original));
+    assert!(!recent.already_seen(&update));
+}
+
+#[test]
+fn least_recently_seen_is_dropped_when_full() {
+    let recent = RecentFindings::new(2);
+    let (ec2, s3, iam) = (
+        fixture("ec2.json"),
+        fixture("s3.json"),
+        fixture("iamuser.json"),
+    );
+
+    recent.already_seen(&ec2);
+    recent.already_seen(&s3);
+    assert!(recent.already_seen(&ec2));
+    recent.already_seen(&iam);
+
+    assert!(recent.already_seen(&ec2));
+    assert!(!recent.already_seen(&s3));
+}
+
+#[test]
+fn forgotten_finding_gets_through() {
+    let recent = RecentFindings::new(10);
+    let message = fixture("ec2.json");
+
+    recent.already_seen(&message);
+    recent.forget(&message);
+    assert!(!recent.already_seen(&message));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::fixture;
+use guarddutyslack::{emf_error_metric, emf_finding_metric, AppError, Config};
+use serde_json::json;
+
+#[test]
+fn finding_metric_counts_the_finding_by_band_and_type() {
+    let now = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+    let metric = emf_finding_metric(&fixture("ec2.json"), &Config::default(), now);
+
+    assert_eq!(
+        metric,
+        json!({
+            "_aws": {
+                "Timestamp": 1640174400000_i64,
+                "CloudWatchMetrics": [{
+                    "Namespace": "GuardDutySlack",
+                    "Dimensions": [["Severity", "FindingType"]],
+                    "Metrics": [{ "Name": "Findings", "Unit": "Count" }],
+                }],
+            },
+            "Severity": "High",
+            "FindingType": "UnauthorizedAccess:EC2/MetadataDNSRebind",
+            "Findings": 1,
+        })
+    );
+}
+
+#[test]
+fn error_metric_counts_the_error_by_type() {
+    let now = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+    let error = AppError::Send {
+        id: String::from("96bef0f2aff363c0dd1466bb22a7e32f"),
+        to: "Slack",
+        reason: String::from("Slack responded with HTTP 500"),
+    };
+
+    assert_eq!(
+        emf_error_metric(&error, now),
+        json!({
+            "_aws": {
+                "Timestamp": 1640174400000_i64,
+                "CloudWatchMetrics": [{
+                    "Namespace": "GuardDutySlack",
+                    "Dimensions": [["ErrorType"]],
+                    "Metrics": [{ "Name": "Errors", "Unit": "Count" }],
+                }],
+            },
+            "ErrorType": "send",
+            "Errors": 1,
+        })
+    );
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::{Config, Skip};
+
+#[test]
+fn suppressed_type_is_skipped() {
+    let mut config = Config::default();
+    config.suppress_types = vec![String::from("unauthorizedaccess:ec2/metadatadnsrebind")];
+
+    assert_eq!(
+        fixture("ec2.json").skip_reason(&config),
+        Some(Skip::SuppressedType)
+    );
+    assert_eq!(fixture("iamuser.json").skip_reason(&config), None);
+}
+
+#[test]
+fn account_allowlist() {
+    let message = fixture("ec2.json");
+
+    // Unset, so every account
+    assert_eq!(message.skip_reason(&Config::default()), None);
+
+    let mut config = Config::default();
+    config.account_allowlist = vec![String::from("123456789012"), String::from("999999999999")];
+    assert_eq!(message.skip_reason(&config), None);
+
+    config.account_allowlist = vec![String::from("123456789012")];
+    assert_eq!(message.skip_reason(&config), Some(Skip::AccountNotAllowed));
+}
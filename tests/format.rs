@@ -0,0 +1,38 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::{AppError, Config, DefaultFormatter, Formatter, Message};
+use serde_json::json;
+use slack_hook3::{Payload, PayloadBuilder};
+
+/// Just the finding type, as a team with its own layout might want
+struct TypeOnly;
+
+impl Formatter for TypeOnly {
+    fn format(&self, message: &Message) -> Result<Payload, AppError> {
+        Ok(PayloadBuilder::new()
+            .text(message.detail.tipe.as_str())
+            .build()
+            .unwrap())
+    }
+}
+
+#[test]
+fn default_formatter_is_build_payload() {
+    let message = fixture("ec2.json");
+    let config = Config::default();
+
+    assert_eq!(
+        json!(DefaultFormatter::new(&config).format(&message).unwrap()),
+        json!(message.build_payload(&config).unwrap())
+    );
+}
+
+#[test]
+fn custom_formatter() {
+    let message = fixture("ec2.json");
+    let payload = json!(TypeOnly.format(&message).unwrap());
+
+    assert_eq!(payload["text"], "UnauthorizedAccess:EC2/MetadataDNSRebind");
+    assert!(payload.get("attachments").is_none());
+}
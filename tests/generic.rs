@@ -0,0 +1,34 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::Config;
+use serde_json::json;
+
+#[test]
+fn normalized_finding() {
+    let finding = fixture("ec2.json").build_normalized(&Config::default());
+
+    assert_eq!(
+        serde_json::to_value(&finding).unwrap(),
+        json!({
+            "id": "96bef0f2aff363c0dd1466bb22a7e32f",
+            "type": "UnauthorizedAccess:EC2/MetadataDNSRebind",
+            "severity": 8.0,
+            "severity_label": "High",
+            "account_id": "999999999999",
+            "account_name": null,
+            "region": "eu-west-2",
+            "title": "EC2 instance i-99999999 may be the target of a DNS rebinding attack.",
+            "description": "EC2 instance i-99999999 is performing DNS lookups that may indicate that it is a target of a DNS rebinding attack.",
+            "console_link": "https://eu-west-2.console.aws.amazon.com/guardduty/home?region=eu-west-2#/findings?fId=96bef0f2aff363c0dd1466bb22a7e32f",
+            "count": 1,
+            "archived": false,
+            "sample": true,
+            "suppressed": 0,
+            "created_at": "2021-12-22T11:27:25.670Z",
+            "updated_at": "2021-12-22T11:27:25.670Z",
+            "first_seen": "2021-12-22T11:27:25Z",
+            "last_seen": "2021-12-22T11:27:25Z",
+        })
+    );
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use common::{fixture_json, mock_slack, sns_record};
+use guarddutyslack::{handle, is_sns_subscribe_url, AppError, Config};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+// The default config has no webhook, so sending to it can only fail
+#[tokio::test]
+async fn slack_failure_fails_the_invocation() {
+    let result = handle(fixture_json("ec2.json"), &Config::default()).await;
+
+    let error = result
+        .expect_err("A failed send should be an Err")
+        .to_string();
+    assert!(error.starts_with("1 of 1 findings failed"), "{}", error);
+    assert!(
+        error.contains("96bef0f2aff363c0dd1466bb22a7e32f"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn unreadable_finding_fails_the_invocation() {
+    let result = handle(json!({ "detail": "not a finding" }), &Config::default()).await;
+
+    let error = result
+        .expect_err("A bad finding should be an Err")
+        .to_string();
+    assert!(error.contains("Failed to deserialize"), "{}", error);
+}
+
+#[tokio::test]
+async fn failures_say_what_kind_they_are() {
+    let error_type = |result: Result<Value, _>| {
+        let error: Box<dyn std::error::Error + Send + Sync> = result.unwrap_err();
+        error.downcast_ref::<AppError>().unwrap().error_type()
+    };
+
+    let unreadable = handle(json!({ "detail": "not a finding" }), &Config::default()).await;
+    assert_eq!(error_type(unreadable), "deserialize");
+
+    let unsent = handle(fixture_json("ec2.json"), &Config::default()).await;
+    assert_eq!(error_type(unsent), "send");
+}
+
+#[tokio::test]
+async fn skipped_finding_is_ok() {
+    let mut config = Config::default();
+    config.suppress_types = vec![String::from("UnauthorizedAccess:EC2/MetadataDNSRebind")];
+
+    let response = handle(fixture_json("ec2.json"), &config).await.unwrap();
+    assert_eq!(response["skipped"], 1);
+    assert_eq!(response["failed"], 0);
+}
+
+#[tokio::test]
+async fn response_says_what_happened_to_each_finding() {
+    let mut config = Config::default();
+    config.suppress_types = vec![String::from("UnauthorizedAccess:EC2/MetadataDNSRebind")];
+
+    let mut not_a_finding = fixture_json("s3.json");
+    not_a_finding["source"] = json!("aws.securityhub");
+
+    let event = json!({
+        "Records": [
+            sns_record(Some(fixture_json("ec2.json").to_string())),
+            sns_record(Some(not_a_finding.to_string())),
+        ]
+    });
+
+    let response = handle(event, &config).await.unwrap();
+    assert_eq!(
+        response,
+        json!({
+            "message": "OK",
+            "total": 2,
+            "sent": 0,
+            "skipped": 2,
+            "failed": 0,
+            "findings": [
+                {
+                    "id": "96bef0f2aff363c0dd1466bb22a7e32f",
+                    "outcome": "skipped",
+                    "reason": "suppressed_type",
+                },
+                {
+                    "id": "f4c1b8e3a2d94b6e8f7a1c2d3e4f5a6b",
+                    "outcome": "skipped",
+                    "reason": "not_guardduty",
+                },
+            ],
+        })
+    );
+}
+
+#[tokio::test]
+async fn sns_record_without_a_message_is_skipped() {
+    let mut config = Config::default();
+    config.suppress_types = vec![String::from("UnauthorizedAccess:EC2/MetadataDNSRebind")];
+
+    let event = json!({
+        "Records": [
+            sns_record(None),
+            sns_record(Some(String::new())),
+            sns_record(Some(fixture_json("ec2.json").to_string())),
+        ]
+    });
+
+    let response = handle(event, &config).await.unwrap();
+    assert_eq!(response["skipped"], 1);
+    assert_eq!(response["failed"], 0);
+}
+
+#[tokio::test]
+async fn waits_for_the_send_before_returning() {
+    let slack = mock_slack(&[200], Duration::from_millis(300));
+    let mut config = Config::default();
+    config.webhook = slack.url.clone();
+
+    let response = handle(fixture_json("ec2.json"), &config).await.unwrap();
+    assert_eq!(slack.answered.load(Ordering::SeqCst), 1);
+    assert_eq!(response["sent"], 1);
+}
+
+#[tokio::test]
+async fn event_from_another_source_is_skipped() {
+    let state_change = json!({
+        "version": "0",
+        "id": "7bf73129-1428-4cd3-a780-95db273d1602",
+        "detail-type": "EC2 Instance State-change Notification",
+        "source": "aws.ec2",
+        "account": "123456789012",
+        "time": "2021-11-11T21:29:54Z",
+        "region": "us-east-1",
+        "resources": ["arn:aws:ec2:us-east-1:123456789012:instance/i-abcd1111"],
+        "detail": { "instance-id": "i-abcd1111", "state": "pending" },
+    });
+
+    // GuardDuty sends more than findings, malware scan results for one
+    let mut scan_result = fixture_json("ec2.json");
+    scan_result["detail-type"] = json!("GuardDuty Malware Protection Object Scan Result");
+
+    for event in [
+        state_change.clone(),
+        json!({ "Records": [sns_record(Some(state_change.to_string()))] }),
+        scan_result,
+    ] {
+        let response = handle(event, &Config::default()).await.unwrap();
+        assert_eq!(response["skipped"], 1);
+        assert_eq!(response["failed"], 0);
+        assert_eq!(response["findings"][0]["reason"], "not_guardduty");
+    }
+}
+
+const TOPIC: &str = "arn:aws:sns:eu-west-2:999999999999:guardduty-findings";
+
+#[test]
+fn only_sns_subscribe_urls_are_confirmed() {
+    let url = "https://sns.eu-west-2.amazonaws.com/?Action=ConfirmSubscription&TopicArn=arn:aws:sns:eu-west-2:999999999999:guardduty-findings&Token=2336412f37";
+    assert!(is_sns_subscribe_url(url, Some(TOPIC)));
+    assert!(is_sns_subscribe_url(url, None));
+
+    // Some other topic, or region
+    assert!(!is_sns_subscribe_url(
+        url,
+        Some("arn:aws:sns:eu-west-2:999999999999:other")
+    ));
+    assert!(!is_sns_subscribe_url(
+        &url.replace("sns.eu-west-2", "sns.us-east-1"),
+        Some(TOPIC)
+    ));
+    // Not SNS at all
+    for url in &[
+        url.replace("https", "http"),
+        url.replace(
+            "sns.eu-west-2.amazonaws.com",
+            "sns.eu-west-2.amazonaws.com.evil.example",
+        ),
+        url.replace("sns.eu-west-2.amazonaws.com", "169.254.169.254"),
+        url.replace(".com/", ".com:8443/"),
+        String::from("not a url"),
+    ] {
+        assert!(!is_sns_subscribe_url(url, Some(TOPIC)), "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn subscription_elsewhere_is_not_confirmed() {
+    let elsewhere = mock_slack(&[200], Duration::from_millis(0));
+    let event = json!({
+        "Type": "SubscriptionConfirmation",
+        "TopicArn": TOPIC,
+        "SubscribeURL": elsewhere.url,
+    });
+
+    let response = handle(event, &Config::default()).await.unwrap();
+    assert_eq!(response["message"], "Subscription not confirmed");
+    assert_eq!(elsewhere.answered.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::fixture_json;
+use guarddutyslack::config::Config;
+use guarddutyslack::format::{build_batch_payload, DefaultFormatter, Formatter};
+use guarddutyslack::model::Message;
+
+#[test]
+fn formatting_through_the_modules() {
+    let message: Message = serde_json::from_value(fixture_json("ec2.json")).unwrap();
+    let config = Config::default();
+
+    let single = DefaultFormatter::new(&config).format(&message).unwrap();
+    let batch = build_batch_payload(std::slice::from_ref(&message), &config).unwrap();
+    assert_eq!(
+        serde_json::to_value(single).unwrap(),
+        serde_json::to_value(batch).unwrap()
+    );
+}
+
+#[test]
+fn finding_link() {
+    let message: Message = serde_json::from_value(fixture_json("ec2.json")).unwrap();
+    assert_eq!(
+        message.finding_link(),
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#unauthorizedaccess-ec2-metadatadnsrebind"
+    );
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::Config;
+
+#[test]
+fn pagerduty_trigger_event() {
+    let event = fixture("ec2.json").build_pagerduty_event(&Config::default(), "routing-key");
+
+    assert_eq!(event["routing_key"], "routing-key");
+    assert_eq!(event["event_action"], "trigger");
+    assert_eq!(event["dedup_key"], "96bef0f2aff363c0dd1466bb22a7e32f");
+
+    let payload = &event["payload"];
+    assert_eq!(payload["severity"], "error");
+    assert_eq!(payload["source"], "999999999999 eu-west-2");
+    assert_eq!(payload["class"], "UnauthorizedAccess:EC2/MetadataDNSRebind");
+    assert_eq!(payload["component"], "Instance");
+
+    assert!(event["links"][0]["href"]
+        .as_str()
+        .unwrap()
+        .starts_with("https://eu-west-2.console.aws.amazon.com/guardduty/"));
+}
+
+#[test]
+fn pagerduty_severity_mapping() {
+    let severity = |score: f32| {
+        let mut message = fixture("ec2.json");
+        message.detail.severity = score;
+        message.build_pagerduty_event(&Config::default(), "key")["payload"]["severity"].clone()
+    };
+
+    assert_eq!(severity(9.5), "critical");
+    assert_eq!(severity(8.0), "error");
+    assert_eq!(severity(5.0), "warning");
+    assert_eq!(severity(2.0), "info");
+}
+
+#[test]
+fn pagerduty_source_names_a_mapped_account() {
+    let mut config = Config::default();
+    config
+        .account_names
+        .insert(String::from("999999999999"), String::from("prod-payments"));
+
+    let event = fixture("ec2.json").build_pagerduty_event(&config, "routing-key");
+    assert_eq!(
+        event["payload"]["source"],
+        "prod-payments (999999999999) eu-west-2"
+    );
+}
@@ -0,0 +1,1113 @@
+mod common;
+
+use common::{fixture, fixture_json};
+use guarddutyslack::{
+    message_to_payload, Config, Message, SeverityRanges, ThresholdMention, MAX_DESCRIPTION_CHARS,
+};
+use serde_json::Value;
+
+/// The payload as the JSON Slack would receive
+fn payload_json(message: &Message) -> Value {
+    serde_json::to_value(message.build_payload(&Config::default()).unwrap()).unwrap()
+}
+
+fn field<'a>(attachment: &'a Value, title: &str) -> Option<&'a str> {
+    attachment["fields"]
+        .as_array()?
+        .iter()
+        .find(|f| f["title"] == title)?["value"]
+        .as_str()
+}
+
+#[test]
+fn deserializes_renamed_fields() {
+    let message = fixture("ec2.json");
+
+    assert_eq!(message.detail_type, "GuardDuty Finding");
+    assert_eq!(
+        message.detail.tipe,
+        "UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(message.detail.schema_version, "2.0");
+    assert_eq!(message.detail.account_id, "999999999999");
+    assert_eq!(
+        message.detail.service.detector_id,
+        "5cbedede2b0b34589ddf6c1149245844"
+    );
+    assert_eq!(message.detail.service.count, 1);
+    assert_eq!(
+        message.detail.service.event_first_seen.to_rfc3339(),
+        "2021-12-22T11:27:25+00:00"
+    );
+}
+
+#[test]
+fn serializes_back_to_the_same_names() {
+    let message = fixture("ec2.json");
+    let json = serde_json::to_value(&message).unwrap();
+
+    assert_eq!(json["detail-type"], "GuardDuty Finding");
+    assert_eq!(
+        json["detail"]["type"],
+        "UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(json["detail"]["schemaVersion"], "2.0");
+    assert_eq!(
+        json["detail"]["service"]["detectorId"],
+        "5cbedede2b0b34589ddf6c1149245844"
+    );
+    assert_eq!(
+        json["detail"]["service"]["eventFirstSeen"],
+        "2021-12-22T11:27:25Z"
+    );
+
+    let again: Message = serde_json::from_value(json).unwrap();
+    assert_eq!(again.detail.schema_version, message.detail.schema_version);
+    assert_eq!(
+        again.detail.service.detector_id,
+        message.detail.service.detector_id
+    );
+    assert_eq!(
+        again.detail.service.event_first_seen,
+        message.detail.service.event_first_seen
+    );
+}
+
+#[test]
+fn ec2_finding() {
+    let json = payload_json(&fixture("ec2.json"));
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(
+        attachment["title"],
+        "(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(
+        attachment["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#unauthorizedaccess-ec2-metadatadnsrebind"
+    );
+    assert_eq!(attachment["color"], "#DB6B30");
+    assert_eq!(
+        attachment["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* @channel"
+    );
+    assert_eq!(field(attachment, "Severity"), Some("8 (High)"));
+    assert_eq!(field(attachment, "Level"), Some("High"));
+    assert_eq!(field(attachment, "Count"), Some("1"));
+    assert_eq!(
+        field(attachment, "First seen"),
+        Some("Wed Dec 22 11:27:25 UTC")
+    );
+    assert_eq!(field(attachment, "Instance"), Some("i-99999999"));
+    assert_eq!(field(attachment, "Action"), Some("DNS_REQUEST"));
+    assert_eq!(
+        field(attachment, "Domain"),
+        Some("GeneratedFindingDomainName")
+    );
+    assert_eq!(field(attachment, "Blocked"), Some("true"));
+    assert_eq!(json["link_names"], 1);
+}
+
+#[test]
+fn repeated_finding_gets_a_badge() {
+    let mut message = fixture("ec2.json");
+    assert_eq!(
+        payload_json(&message)["attachments"][0]["title"],
+        "(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+
+    message.detail.service.count = 400;
+    assert_eq!(
+        payload_json(&message)["attachments"][0]["title"],
+        "(SAMPLE) 🔁 x400 UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+}
+
+#[test]
+fn message_to_payload_uses_default_config() {
+    let message = fixture("ec2.json");
+    assert_eq!(
+        serde_json::to_value(message_to_payload(&message).unwrap()).unwrap(),
+        payload_json(&message)
+    );
+}
+
+#[test]
+fn long_description_is_truncated() {
+    let mut message = fixture("ec2.json");
+    message.detail.description = "x".repeat(MAX_DESCRIPTION_CHARS * 2);
+
+    let json = payload_json(&message);
+    let text = json["attachments"][0]["text"].as_str().unwrap();
+    assert!(text.starts_with(&"x".repeat(MAX_DESCRIPTION_CHARS - 50)));
+    assert!(text.ends_with("\n… see console for full detail"));
+    assert!(text.chars().count() <= MAX_DESCRIPTION_CHARS);
+}
+
+#[test]
+fn long_raw_finding_is_truncated() {
+    let mut message = fixture("ec2.json");
+    message.detail.description = "x".repeat(MAX_DESCRIPTION_CHARS * 2);
+    let mut config = Config::default();
+    config.verbose = true;
+
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    let raw = json["attachments"][1]["text"].as_str().unwrap();
+    let raw = raw.trim_start_matches("```").trim_end_matches("```");
+    assert!(raw.ends_with("\n… see console for full detail"));
+    assert!(raw.chars().count() <= MAX_DESCRIPTION_CHARS);
+}
+
+#[test]
+fn region_gets_a_friendly_name() {
+    let mut config = Config::default();
+    config
+        .region_names
+        .insert(String::from("eu-west-2"), String::from("London"));
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in London (eu-west-2) from account 999999999999* @channel"
+    );
+}
+
+fn console_link(partition: &str, region: &str) -> String {
+    let mut message = fixture("ec2.json");
+    message.detail.partition = partition.to_string();
+    message.detail.region = region.to_string();
+
+    let json = payload_json(&message);
+    field(&json["attachments"][0], "Console")
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn console_link_for_each_partition() {
+    assert!(console_link("aws", "eu-west-2")
+        .starts_with("<https://eu-west-2.console.aws.amazon.com/guardduty/home?region=eu-west-2#"));
+    assert!(console_link("aws-us-gov", "us-gov-west-1")
+        .starts_with("<https://console.amazonaws-us-gov.com/guardduty/home?region=us-gov-west-1#"));
+    assert!(console_link("aws-cn", "cn-north-1")
+        .starts_with("<https://console.amazonaws.cn/guardduty/home?region=cn-north-1#"));
+}
+
+#[test]
+fn severity_emoji_prefixes_the_title() {
+    let mut config = Config::default();
+    config.severity_emoji = true;
+
+    let title = |name: &str| {
+        serde_json::to_value(fixture(name).build_payload(&config).unwrap()).unwrap()["attachments"]
+            [0]["title"]
+            .clone()
+    };
+    assert_eq!(
+        title("ec2.json"),
+        "🟠 (SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(
+        title("iamuser.json"),
+        "🟡 (SAMPLE) CredentialAccess:IAMUser/AnomalousBehavior"
+    );
+    assert_eq!(
+        title("s3.json"),
+        "🔵 Policy:S3/BucketBlockPublicAccessDisabled"
+    );
+}
+
+#[test]
+fn missing_or_out_of_range_severity_is_unknown() {
+    let unknown = |message: &Message| {
+        let json = payload_json(message);
+        let attachment = &json["attachments"][0];
+        attachment["color"] == "#BABABA"
+            && field(attachment, "Level") == Some("Unknown")
+            && attachment["pretext"] == "*New finding in eu-west-2 from account 999999999999* "
+    };
+
+    let mut raw = fixture_json("ec2.json");
+    raw["detail"].as_object_mut().unwrap().remove("severity");
+    let message: Message = serde_json::from_value(raw).unwrap();
+    assert_eq!(message.detail.severity, 0.0);
+    assert!(unknown(&message));
+
+    let mut message = fixture("ec2.json");
+    message.detail.severity = 0.0;
+    assert!(unknown(&message));
+
+    message.detail.severity = 11.0;
+    assert!(unknown(&message));
+
+    // The top of the range is still critical
+    message.detail.severity = 10.0;
+    assert_eq!(payload_json(&message)["attachments"][0]["color"], "#DF4661");
+}
+
+#[test]
+fn custom_footer() {
+    let attachment = |config: &Config| {
+        serde_json::to_value(fixture("ec2.json").build_payload(config).unwrap()).unwrap()
+            ["attachments"][0]
+            .clone()
+    };
+
+    let mut config = Config::default();
+    assert_eq!(attachment(&config)["footer"], "GuardyBot");
+    assert_eq!(
+        attachment(&config)["footer_icon"],
+        "https://rustacean.net/assets/rustacean-flat-happy.png"
+    );
+
+    config.footer_text = String::from("SecBot");
+    config.footer_icon = None;
+    assert_eq!(attachment(&config)["footer"], "SecBot");
+    assert!(attachment(&config).get("footer_icon").is_none());
+}
+
+#[test]
+fn link_names_can_be_turned_off() {
+    let mut config = Config::default();
+    config.link_names = false;
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    assert_eq!(json["link_names"], 0);
+}
+
+#[test]
+fn detection_lag() {
+    let lag = |message: &Message| {
+        field(&payload_json(message)["attachments"][0], "Detection lag").map(String::from)
+    };
+
+    // First seen 10:45:36, created 11:02:10
+    let mut message = fixture("s3.json");
+    assert_eq!(lag(&message).as_deref(), Some("16m"));
+
+    message.detail.service.event_first_seen = "2021-12-23T08:30:00Z".parse().unwrap();
+    assert_eq!(lag(&message).as_deref(), Some("2h 32m"));
+
+    message.detail.service.event_first_seen = "2021-12-20T08:30:00Z".parse().unwrap();
+    assert_eq!(lag(&message).as_deref(), Some("3d 2h"));
+
+    message.detail.service.event_first_seen = "2021-12-23T11:02:00Z".parse().unwrap();
+    assert_eq!(lag(&message).as_deref(), Some("10s"));
+
+    message.detail.service.event_first_seen = "2021-12-23T12:00:00Z".parse().unwrap();
+    assert_eq!(lag(&message).as_deref(), Some("0s (clock skew)"));
+}
+
+#[test]
+fn active_span() {
+    // First and last seen 16:45:01 and 16:56:25
+    let mut message = fixture("kubernetes.json");
+    assert_eq!(message.active_span().as_deref(), Some("active over 11m"));
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Last seen"),
+        Some("Tue Feb  8 16:56:25 UTC (active over 11m)")
+    );
+
+    message.detail.service.event_last_seen = "2022-02-11T21:00:00Z".parse().unwrap();
+    assert_eq!(message.active_span().as_deref(), Some("active over 3d 4h"));
+
+    message.detail.service.event_last_seen = "2022-02-08T16:45:31Z".parse().unwrap();
+    assert_eq!(message.active_span().as_deref(), Some("active over 30s"));
+}
+
+#[test]
+fn seen_once_has_no_active_span() {
+    let message = fixture("ec2.json");
+    assert_eq!(message.active_span(), None);
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Last seen"),
+        Some("Wed Dec 22 11:27:25 UTC")
+    );
+}
+
+#[test]
+fn fields_are_short_by_default() {
+    let json = payload_json(&fixture("ec2.json"));
+    let fields = json["attachments"][0]["fields"].as_array().unwrap();
+    let severity = fields.iter().find(|f| f["title"] == "Severity").unwrap();
+    assert_eq!(severity["short"], true);
+}
+
+#[test]
+fn fields_are_full_width_without_fields_short() {
+    let mut config = Config::default();
+    config.fields_short = false;
+    config.include_ids = true;
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    let fields = json["attachments"][0]["fields"].as_array().unwrap();
+    assert!(fields.iter().all(|f| f["short"] == false), "{:?}", fields);
+}
+
+#[test]
+fn channel_override() {
+    assert!(payload_json(&fixture("ec2.json")).get("channel").is_none());
+
+    let mut config = Config::default();
+    config.channel = Some(String::from("#security-alerts"));
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    assert_eq!(json["channel"], "#security-alerts");
+}
+
+#[test]
+fn sample_findings_are_marked() {
+    let sample = fixture("kubernetes.json");
+    assert!(sample.is_sample());
+    assert!(payload_json(&sample)["attachments"][0]["title"]
+        .as_str()
+        .unwrap()
+        .starts_with("(SAMPLE) "));
+
+    let real = fixture("s3.json");
+    assert!(!real.is_sample());
+    assert_eq!(
+        payload_json(&real)["attachments"][0]["title"],
+        "Policy:S3/BucketBlockPublicAccessDisabled"
+    );
+}
+
+#[test]
+fn threat_list_is_shown() {
+    let message = fixture("ec2.json");
+    assert_eq!(
+        message.threat_list(),
+        Some("GeneratedFindingThreatListName")
+    );
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Threat list"),
+        Some("GeneratedFindingThreatListName")
+    );
+
+    let message = fixture("s3.json");
+    assert_eq!(message.threat_list(), None);
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Threat list"),
+        None
+    );
+}
+
+#[test]
+fn verbose_adds_the_raw_finding() {
+    assert_eq!(
+        payload_json(&fixture("ec2.json"))["attachments"]
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let mut config = Config::default();
+    config.verbose = true;
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    let raw = json["attachments"][1]["text"].as_str().unwrap();
+
+    assert!(raw.starts_with("```{"));
+    assert!(raw.ends_with("```"));
+    // Serialized with the same names GuardDuty uses
+    assert!(raw.contains(r#""detail-type": "GuardDuty Finding""#));
+    assert!(raw.contains(r#""type": "UnauthorizedAccess:EC2/MetadataDNSRebind""#));
+    assert!(raw.contains(r#""instanceId": "i-99999999""#));
+}
+
+#[test]
+fn iamuser_finding() {
+    let json = payload_json(&fixture("iamuser.json"));
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(
+        attachment["title"],
+        "(SAMPLE) CredentialAccess:IAMUser/AnomalousBehavior"
+    );
+    assert_eq!(
+        attachment["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-iam.html#credentialaccess-iam-anomalousbehavior"
+    );
+    assert_eq!(attachment["color"], "#FED141");
+    assert_eq!(field(attachment, "Resource type"), Some("AccessKey"));
+    assert_eq!(
+        field(attachment, "Access key"),
+        Some("GeneratedFindingAccessKeyId")
+    );
+    assert_eq!(field(attachment, "User"), Some("GeneratedFindingUserName"));
+}
+
+#[test]
+fn s3_finding() {
+    let json = payload_json(&fixture("s3.json"));
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(
+        attachment["title"],
+        "Policy:S3/BucketBlockPublicAccessDisabled"
+    );
+    assert_eq!(
+        attachment["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-s3.html#policy-s3-bucketblockpublicaccessdisabled"
+    );
+    assert_eq!(attachment["color"], "#00A3E0");
+    assert_eq!(field(attachment, "Level"), Some("Low"));
+    assert_eq!(
+        attachment["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+    assert_eq!(field(attachment, "Bucket"), Some("example-bucket"));
+    assert_eq!(field(attachment, "Action"), Some("AWS_API_CALL"));
+    assert_eq!(field(attachment, "API"), Some("PutBucketPublicAccessBlock"));
+    assert_eq!(field(attachment, "Remote IP"), Some("86.15.200.19"));
+    assert_eq!(field(attachment, "Country"), Some("United Kingdom"));
+}
+
+#[test]
+fn kubernetes_finding() {
+    let json = payload_json(&fixture("kubernetes.json"));
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(
+        attachment["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-kubernetes.html#privilegeescalation-kubernetes-privilegedcontainer"
+    );
+    assert_eq!(field(attachment, "Count"), Some("2"));
+    assert_eq!(
+        field(attachment, "EKS cluster"),
+        Some("GeneratedFindingEKSClusterName")
+    );
+    assert_eq!(field(attachment, "Action"), Some("KUBERNETES_API_CALL"));
+    assert_eq!(field(attachment, "Verb"), Some("create"));
+}
+
+fn title_link(finding_type: &str) -> String {
+    let mut message = fixture("ec2.json");
+    message.detail.tipe = finding_type.to_string();
+    payload_json(&message)["attachments"][0]["title_link"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn finding_link_edge_cases() {
+    let all_types =
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-active.html";
+
+    assert_eq!(
+        title_link("Recon:EC2/PortProbeUnprotectedPort"),
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#recon-ec2-portprobeunprotectedport"
+    );
+    // No slash means no group to pick a page with
+    assert_eq!(title_link("UnauthorizedAccess:IAMUser"), all_types);
+    // The group is whatever sits between the last colon and the slash
+    assert!(
+        title_link("Trojan:Extra:EC2/DNSDataExfiltration").starts_with(
+            "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#"
+        )
+    );
+    assert_eq!(title_link("Recon:Mainframe/PortProbe"), all_types);
+    assert_eq!(title_link(""), all_types);
+}
+
+#[test]
+fn finding_link_for_each_group() {
+    let docs = "https://docs.aws.amazon.com/guardduty/latest/ug/";
+    let cases = [
+        (
+            "Persistence:IAMUser/AnomalousBehavior",
+            "guardduty_finding-types-iam.html#persistence-iam-anomalousbehavior",
+        ),
+        (
+            "Backdoor:EC2/C&CActivity.B",
+            "guardduty_finding-types-ec2.html#backdoor-ec2-c&cactivity.b",
+        ),
+        (
+            "Stealth:S3/ServerAccessLoggingDisabled",
+            "guardduty_finding-types-s3.html#stealth-s3-serveraccessloggingdisabled",
+        ),
+        (
+            "Discovery:Kubernetes/SuccessfulAnonymousAccess",
+            "guardduty_finding-types-kubernetes.html#discovery-kubernetes-successfulanonymousaccess",
+        ),
+        (
+            "CredentialAccess:RDS/AnomalousBehavior.SuccessfulLogin",
+            "findings-rds-protection.html#credentialaccess-rds-anomalousbehavior.successfullogin",
+        ),
+        (
+            "CryptoCurrency:Lambda/BitcoinTool.B",
+            "lambda-protection-finding-types.html#cryptocurrency-lambda-bitcointool.b",
+        ),
+        (
+            "Execution:Runtime/NewBinaryExecuted",
+            "findings-runtime-monitoring.html#execution-runtime-newbinaryexecuted",
+        ),
+        (
+            "Execution:ECS/MaliciousFile",
+            "findings-malware-protection.html#execution-ecs-maliciousfile",
+        ),
+        (
+            "Execution:Container/SuspiciousFile",
+            "findings-malware-protection.html#execution-container-suspiciousfile",
+        ),
+    ];
+    for (finding_type, link) in &cases {
+        assert_eq!(title_link(finding_type), format!("{}{}", docs, link));
+    }
+}
+
+#[test]
+fn finding_link_normalizes_the_group() {
+    // Hyphens and case don't change which group it is, the anchor uses the group's own name
+    assert_eq!(
+        title_link("Persistence:IAM-User/AnomalousBehavior"),
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-iam.html#persistence-iam-anomalousbehavior"
+    );
+    // Digits are part of the group, so an unmapped one with digits still falls back
+    assert_eq!(
+        title_link("Recon:EC3/PortProbe"),
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-active.html"
+    );
+}
+
+#[test]
+fn ids_only_with_include_ids() {
+    let message = fixture("ec2.json");
+    let json = payload_json(&message);
+    assert_eq!(field(&json["attachments"][0], "Detector ID"), None);
+    assert_eq!(field(&json["attachments"][0], "Finding ARN"), None);
+
+    let mut config = Config::default();
+    config.include_ids = true;
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    let attachment = &json["attachments"][0];
+    assert_eq!(
+        field(attachment, "Detector ID"),
+        Some("5cbedede2b0b34589ddf6c1149245844")
+    );
+    assert_eq!(
+        field(attachment, "Finding ARN"),
+        Some("arn:aws:guardduty:eu-west-2:999999999999:detector/5cbedede2b0b34589ddf6c1149245844/finding/96bef0f2aff363c0dd1466bb22a7e32f")
+    );
+}
+
+#[test]
+fn mapped_type_gets_a_friendly_title() {
+    let mut config = Config::default();
+    config.titles.insert(
+        String::from("UnauthorizedAccess:EC2/MetadataDNSRebind"),
+        String::from("DNS rebinding against EC2"),
+    );
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    let attachment = &json["attachments"][0];
+    assert_eq!(attachment["title"], "(SAMPLE) DNS rebinding against EC2");
+    // The type is still there to search for
+    assert_eq!(
+        field(attachment, "Finding type"),
+        Some("UnauthorizedAccess:EC2/MetadataDNSRebind")
+    );
+}
+
+#[test]
+fn unmapped_type_is_the_title() {
+    let mut config = Config::default();
+    config.titles.insert(
+        String::from("UnauthorizedAccess:EC2/MetadataDNSRebind"),
+        String::from("DNS rebinding against EC2"),
+    );
+
+    let json = serde_json::to_value(fixture("s3.json").build_payload(&config).unwrap()).unwrap();
+    let attachment = &json["attachments"][0];
+    assert_eq!(
+        attachment["title"],
+        "Policy:S3/BucketBlockPublicAccessDisabled"
+    );
+    assert_eq!(
+        field(attachment, "Finding type"),
+        Some("Policy:S3/BucketBlockPublicAccessDisabled")
+    );
+}
+
+#[test]
+fn templates_are_filled_in() {
+    let message = fixture("ec2.json");
+
+    assert_eq!(
+        message.fill_template("{id}"),
+        "96bef0f2aff363c0dd1466bb22a7e32f"
+    );
+    assert_eq!(message.fill_template("{account}"), "999999999999");
+    assert_eq!(message.fill_template("{region}"), "eu-west-2");
+    assert_eq!(
+        message.fill_template("{type}"),
+        "UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(
+        message.fill_template("https://siem.example.com/{account}/{region}?q={id}&t={type}&x={other}"),
+        "https://siem.example.com/999999999999/eu-west-2?q=96bef0f2aff363c0dd1466bb22a7e32f&t=UnauthorizedAccess:EC2/MetadataDNSRebind&x={other}"
+    );
+}
+
+#[test]
+fn extra_fields_are_appended() {
+    let mut config = Config::default();
+    config.extra_fields = vec![
+        (String::from("Owner"), String::from("Platform team")),
+        (
+            String::from("SIEM"),
+            String::from("https://siem.example.com/search?q={id}"),
+        ),
+    ];
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    let fields = json["attachments"][0]["fields"].as_array().unwrap();
+    let last: Vec<(&str, &str)> = fields[fields.len() - 2..]
+        .iter()
+        .map(|f| (f["title"].as_str().unwrap(), f["value"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        last,
+        vec![
+            ("Owner", "Platform team"),
+            (
+                "SIEM",
+                "https://siem.example.com/search?q=96bef0f2aff363c0dd1466bb22a7e32f"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn runbook_replaces_the_docs_link() {
+    let mut config = Config::default();
+    config.runbooks.insert(
+        String::from("UnauthorizedAccess:EC2/MetadataDNSRebind"),
+        String::from("https://wiki.example.com/runbooks/dns-rebind"),
+    );
+    config.runbooks.insert(
+        String::from("UnauthorizedAccess:"),
+        String::from("https://wiki.example.com/runbooks/unauthorized"),
+    );
+    config.runbooks.insert(
+        String::from("policy:s3/"),
+        String::from("https://wiki.example.com/runbooks/s3-policy"),
+    );
+    let link = |name: &str| {
+        let json = serde_json::to_value(fixture(name).build_payload(&config).unwrap()).unwrap();
+        json["attachments"][0]["title_link"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    // The exact type beats the shorter prefix it also starts with
+    assert_eq!(
+        link("ec2.json"),
+        "https://wiki.example.com/runbooks/dns-rebind"
+    );
+    // Prefixes match ignoring case
+    assert_eq!(
+        link("s3.json"),
+        "https://wiki.example.com/runbooks/s3-policy"
+    );
+    // Nothing matches, so it's the docs
+    assert_eq!(
+        link("iamuser.json"),
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-iam.html#credentialaccess-iam-anomalousbehavior"
+    );
+}
+
+#[test]
+fn unknown_finding_type_links_to_all_finding_types() {
+    let message = fixture("unknown_type.json");
+    assert_eq!(message.detail.tipe, "Unknown:Future/NewFindingType");
+
+    let json = payload_json(&message);
+    assert_eq!(
+        json["attachments"][0]["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-active.html"
+    );
+}
+
+#[test]
+fn malware_finding() {
+    let json = payload_json(&fixture("malware.json"));
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(
+        attachment["title_link"],
+        "https://docs.aws.amazon.com/guardduty/latest/ug/findings-malware-protection.html#execution-ec2-maliciousfile"
+    );
+    assert_eq!(field(attachment, "Instance"), Some("i-99999999"));
+    assert_eq!(field(attachment, "Threat"), Some("Trojan.Linux.Mirai"));
+    assert_eq!(field(attachment, "Infected files"), Some("2"));
+    assert_eq!(field(attachment, "File"), Some("/usr/bin/.sshd"));
+    assert_eq!(field(attachment, "Action"), None);
+}
+
+#[test]
+fn threshold_mention_from_the_threshold_up() {
+    let mut config = Config::default();
+    config.threshold_mention = Some(ThresholdMention {
+        min_severity: 7.5,
+        text: String::from("@security-oncall"),
+    });
+
+    let pretext = |severity: f32| {
+        let mut json = fixture_json("ec2.json");
+        json["detail"]["severity"] = severity.into();
+        let message: Message = serde_json::from_value(json).unwrap();
+        serde_json::to_value(message.build_payload(&config).unwrap()).unwrap()["attachments"][0]
+            ["pretext"]
+            .clone()
+    };
+    let prefix = "*New finding in eu-west-2 from account 999999999999*";
+
+    assert_eq!(pretext(7.4), format!("{} @channel", prefix));
+    assert_eq!(
+        pretext(7.5),
+        format!("{} @channel @security-oncall", prefix)
+    );
+    assert_eq!(
+        pretext(8.0),
+        format!("{} @channel @security-oncall", prefix)
+    );
+}
+
+#[test]
+fn threshold_mention_is_not_doubled_up() {
+    let mut config = Config::default();
+    config.threshold_mention = Some(ThresholdMention {
+        min_severity: 5.0,
+        text: String::from("@channel @security-oncall"),
+    });
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* @channel @security-oncall"
+    );
+}
+
+#[test]
+fn mention_severities_range() {
+    let ranges = SeverityRanges::parse("7.0-10.0").unwrap();
+    assert!(!ranges.contains(6.9));
+    assert!(ranges.contains(7.0));
+    assert!(ranges.contains(8.5));
+    assert!(ranges.contains(10.0));
+
+    let mut config = Config::default();
+    config.mention_severities = Some(ranges);
+    let pretext = |severity: f32| {
+        let mut json = fixture_json("iamuser.json");
+        json["detail"]["severity"] = severity.into();
+        let message: Message = serde_json::from_value(json).unwrap();
+        serde_json::to_value(message.build_payload(&config).unwrap()).unwrap()["attachments"][0]
+            ["pretext"]
+            .clone()
+    };
+    let prefix = "*New finding in eu-west-2 from account 999999999999*";
+
+    // Medium would be @here, but it isn't listed
+    assert_eq!(pretext(5.0), format!("{} ", prefix));
+    assert_eq!(pretext(7.0), format!("{} @channel", prefix));
+}
+
+#[test]
+fn mention_severities_values() {
+    let ranges = SeverityRanges::parse(" 5, 5.5 ,9-10").unwrap();
+    assert!(ranges.contains(5.0));
+    assert!(ranges.contains(5.5));
+    assert!(!ranges.contains(5.2));
+    assert!(!ranges.contains(8.0));
+    assert!(ranges.contains(9.5));
+
+    assert!(SeverityRanges::parse("").is_err());
+    assert!(SeverityRanges::parse("high").is_err());
+    assert!(SeverityRanges::parse("8-4").is_err());
+}
+
+#[test]
+fn first_sighting_is_a_new_finding() {
+    let message = fixture("ec2.json");
+    assert_eq!(message.detail.created_at, message.detail.updated_at);
+    assert!(message.is_new());
+    assert!(payload_json(&message)["attachments"][0]["pretext"]
+        .as_str()
+        .unwrap()
+        .starts_with("*New finding in"));
+}
+
+#[test]
+fn seen_again_is_an_updated_finding() {
+    // Seen twice, the second time some minutes after it was created
+    let message = fixture("kubernetes.json");
+    assert_ne!(message.detail.created_at, message.detail.updated_at);
+    assert!(!message.is_new());
+    assert!(payload_json(&message)["attachments"][0]["pretext"]
+        .as_str()
+        .unwrap()
+        .starts_with("*Updated finding in"));
+
+    // Still the one sighting, so it's new whatever the timestamps say
+    let mut message = fixture("kubernetes.json");
+    message.detail.service.count = 1;
+    assert!(message.is_new());
+
+    // Unless it's since been archived
+    message.detail.service.archived = true;
+    assert!(!message.is_new());
+}
+
+#[test]
+fn suppressed_count_is_shown() {
+    let mut message = fixture("ec2.json");
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Suppressed"),
+        None
+    );
+
+    message.suppressed = 4;
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Suppressed"),
+        Some("4 more of this type")
+    );
+}
+
+#[test]
+fn newer_schema_still_parses() {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["schemaVersion"] = "3.0".into();
+    json["detail"]["riskScore"] = 42.into();
+    json["detail"]["service"]["detection"] = serde_json::json!({ "sequence": "abc" });
+    let message: Message = serde_json::from_value(json).unwrap();
+
+    assert!(!message.schema_is_known());
+    assert!(fixture("ec2.json").schema_is_known());
+
+    // The new fields survive for VERBOSE
+    let raw = serde_json::to_value(&message).unwrap();
+    assert_eq!(raw["detail"]["riskScore"], 42);
+    assert_eq!(raw["detail"]["service"]["detection"]["sequence"], "abc");
+    assert_eq!(
+        payload_json(&message)["attachments"][0]["title"],
+        "(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+}
+
+#[test]
+fn resource_tags_are_shown() {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["resource"]["instanceDetails"]["tags"] = serde_json::json!([
+        { "key": "Name", "value": "web-1" },
+        { "key": "team", "value": "payments" },
+        { "key": "Owner", "value": "alice@example.com" },
+    ]);
+    let message: Message = serde_json::from_value(json).unwrap();
+    let json = payload_json(&message);
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(field(attachment, "Owner"), Some("alice@example.com"));
+    assert_eq!(field(attachment, "Team"), Some("payments"));
+    assert_eq!(field(attachment, "Environment"), None);
+    assert_eq!(field(attachment, "Name"), None);
+
+    let mut config = Config::default();
+    config.tag_keys = vec![String::from("Name")];
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    assert_eq!(field(&json["attachments"][0], "Name"), Some("web-1"));
+    assert_eq!(field(&json["attachments"][0], "Owner"), None);
+}
+
+#[test]
+fn bucket_tags_are_shown() {
+    let mut json = fixture_json("s3.json");
+    json["detail"]["resource"]["s3BucketDetails"][0]["tags"] =
+        serde_json::json!([{ "key": "Environment", "value": "prod" }]);
+    let message: Message = serde_json::from_value(json).unwrap();
+
+    assert_eq!(
+        field(&payload_json(&message)["attachments"][0], "Environment"),
+        Some("prod")
+    );
+}
+
+#[test]
+fn resolved_update_for_archived_finding() {
+    let mut message = fixture("ec2.json");
+    message.detail.service.archived = true;
+
+    // Greyed out by default
+    let json = payload_json(&message);
+    assert_eq!(json["attachments"][0]["color"], "#BABABA");
+    assert_eq!(field(&json["attachments"][0], "Resolved"), None);
+
+    let mut config = Config::default();
+    config.notify_resolved = true;
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    let attachment = &json["attachments"][0];
+
+    assert_eq!(attachment["color"], "#008C95");
+    assert_eq!(
+        attachment["title"],
+        "Resolved: (SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(field(attachment, "Level"), Some("Resolved"));
+    assert_eq!(
+        field(attachment, "Resolved"),
+        Some("Wed Dec 22 11:27:25 UTC")
+    );
+    assert_eq!(
+        attachment["pretext"],
+        "*New finding in eu-west-2 from account 999999999999* "
+    );
+}
+
+#[test]
+fn api_call_says_who_called_what() {
+    let json = payload_json(&fixture("s3.json"));
+    let attachment = &json["attachments"][0];
+    let text = attachment["text"].as_str().unwrap();
+
+    assert!(
+        text.starts_with("*AssumedRole OrganizationAccountAccessRole called PutBucketPublicAccessBlock on s3.amazonaws.com*\n"),
+        "{}",
+        text
+    );
+    assert_eq!(field(attachment, "Caller type"), Some("Remote IP"));
+
+    let json = payload_json(&fixture("iamuser.json"));
+    let text = json["attachments"][0]["text"].as_str().unwrap();
+    assert!(text.contains("(AccessDenied)*"), "{}", text);
+
+    // Not an API call, so the description is left alone
+    let message = fixture("ec2.json");
+    assert_eq!(
+        payload_json(&message)["attachments"][0]["text"],
+        message.detail.description
+    );
+}
+
+#[test]
+fn api_caller_with_only_an_access_key() {
+    let mut json = fixture_json("s3.json");
+    json["detail"]["resource"]["accessKeyDetails"] =
+        serde_json::json!({ "accessKeyId": "AKIAEXAMPLE" });
+    json["detail"]["service"]["action"]["awsApiCallAction"]
+        .as_object_mut()
+        .unwrap()
+        .remove("serviceName");
+    let message: Message = serde_json::from_value(json).unwrap();
+
+    let json = payload_json(&message);
+    let text = json["attachments"][0]["text"].as_str().unwrap();
+    assert!(
+        text.starts_with("*AKIAEXAMPLE called PutBucketPublicAccessBlock*\n"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn severity_shows_the_band() {
+    let severity = |value: f32, archived: bool| {
+        let mut message = fixture("ec2.json");
+        message.detail.severity = value;
+        message.detail.service.archived = archived;
+        let json = payload_json(&message);
+        field(&json["attachments"][0], "Severity").map(String::from)
+    };
+
+    assert_eq!(severity(7.3, false).as_deref(), Some("7.3 (High)"));
+    assert_eq!(severity(9.0, false).as_deref(), Some("9 (Critical)"));
+    assert_eq!(severity(2.5, false).as_deref(), Some("2.5 (Low)"));
+    assert_eq!(severity(0.0, false).as_deref(), Some("0 (Unknown)"));
+    assert_eq!(severity(5.0, true).as_deref(), Some("5 (Medium)"));
+}
+
+#[test]
+fn batches_are_capped_by_attachments() {
+    let mut config = Config::default();
+    assert_eq!(config.batch_size(), 20);
+
+    config.max_attachments = 5;
+    assert_eq!(config.batch_size(), 5);
+
+    // VERBOSE adds the raw finding as a second attachment
+    config.verbose = true;
+    assert_eq!(config.batch_size(), 2);
+
+    // Too small for a whole finding, but it still has to go somewhere
+    config.max_attachments = 1;
+    assert_eq!(config.batch_size(), 1);
+    config.max_attachments = 0;
+    assert_eq!(config.batch_size(), 1);
+}
+
+#[test]
+fn ts_is_the_update_time_as_epoch_seconds() {
+    let json = payload_json(&fixture("ec2.json"));
+    // 2021-12-22T11:27:25.670Z
+    assert_eq!(json["attachments"][0]["ts"], 1640172445);
+
+    // The same instant written with an offset is the same ts
+    let mut finding = fixture_json("ec2.json");
+    finding["detail"]["updatedAt"] = Value::from("2021-12-22T12:27:25.670+01:00");
+    let message: Message = serde_json::from_value(finding).unwrap();
+    assert_eq!(payload_json(&message)["attachments"][0]["ts"], 1640172445);
+}
+
+#[test]
+fn description_markup_is_escaped() {
+    let mut message = fixture("ec2.json");
+    message.detail.description = String::from("<script>alert(1)</script> & <!channel>");
+    let json = payload_json(&message);
+
+    assert_eq!(
+        json["attachments"][0]["text"],
+        "&lt;script&gt;alert(1)&lt;/script&gt; &amp; &lt;!channel&gt;"
+    );
+}
+
+#[test]
+fn unbuildable_attachment_is_an_error() {
+    let mut config = Config::default();
+    config.footer_icon = Some(String::from("not a url"));
+
+    let error = fixture("ec2.json").build_payload(&config).unwrap_err();
+    assert_eq!(error.error_type(), "format");
+    assert!(error
+        .to_string()
+        .starts_with("Failed to build the Slack message for 96bef0f2aff363c0dd1466bb22a7e32f"));
+}
+
+#[test]
+fn mapped_account_is_shown_by_name() {
+    let mut config = Config::default();
+    config
+        .account_names
+        .insert(String::from("999999999999"), String::from("prod-payments"));
+
+    let json = serde_json::to_value(fixture("ec2.json").build_payload(&config).unwrap()).unwrap();
+    let attachment = &json["attachments"][0];
+    assert_eq!(
+        attachment["pretext"],
+        "*New finding in eu-west-2 from account prod-payments (999999999999)* @channel"
+    );
+    assert_eq!(
+        attachment["fallback"],
+        "GuardDuty:UnauthorizedAccess:EC2/MetadataDNSRebind in prod-payments (999999999999) eu-west-2"
+    );
+
+    // Another account isn't in the map, so it's just the ID
+    let mut other = fixture_json("ec2.json");
+    other["detail"]["accountId"] = Value::from("123456789012");
+    let message: Message = serde_json::from_value(other).unwrap();
+    let json = serde_json::to_value(message.build_payload(&config).unwrap()).unwrap();
+    assert_eq!(
+        json["attachments"][0]["pretext"],
+        "*New finding in eu-west-2 from account 123456789012* @channel"
+    );
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use chrono::{NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use common::fixture;
+use guarddutyslack::QuietHours;
+
+fn time(h: u32, m: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(h, m, 0).unwrap()
+}
+
+#[test]
+fn window_in_the_day() {
+    let quiet = QuietHours::new(time(12, 0), time(14, 0), Tz::UTC);
+
+    assert!(!quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 11, 59, 0).unwrap()));
+    assert!(quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap()));
+    assert!(quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 13, 30, 0).unwrap()));
+    assert!(!quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 14, 0, 0).unwrap()));
+}
+
+#[test]
+fn window_over_midnight() {
+    let quiet = QuietHours::new(time(22, 0), time(7, 0), Tz::UTC);
+
+    assert!(quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 23, 0, 0).unwrap()));
+    assert!(quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 3, 0, 0).unwrap()));
+    assert!(!quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 7, 0, 0).unwrap()));
+    assert!(!quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap()));
+}
+
+#[test]
+fn window_in_its_own_zone() {
+    // 22:00 to 07:00 in Sydney, which is UTC+11 in December
+    let quiet = QuietHours::new(time(22, 0), time(7, 0), Tz::Australia__Sydney);
+
+    assert!(quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 16, 0, 0).unwrap()));
+    assert!(!quiet.contains(Utc.with_ymd_and_hms(2021, 12, 22, 23, 0, 0).unwrap()));
+}
+
+#[test]
+fn critical_findings_go_through() {
+    let quiet = QuietHours::new(time(22, 0), time(7, 0), Tz::UTC);
+    let night = Utc.with_ymd_and_hms(2021, 12, 22, 3, 0, 0).unwrap();
+    let day = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    // ec2.json is severity 8
+    let message = fixture("ec2.json");
+    assert!(quiet.holds_back(&message, 9.0, night));
+    assert!(!quiet.holds_back(&message, 9.0, day));
+    assert!(!quiet.holds_back(&message, 8.0, night));
+}
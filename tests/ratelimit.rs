@@ -0,0 +1,165 @@
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use common::fixture;
+use guarddutyslack::{HeldBack, Limit, RateLimit, Route};
+
+#[test]
+fn suppressed_after_the_limit() {
+    let limit = RateLimit::new(2, Duration::minutes(5));
+    let message = fixture("ec2.json");
+    let now = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Suppress
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Suppress
+    );
+}
+
+#[test]
+fn next_window_carries_the_suppressed_count() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let message = fixture("ec2.json");
+    let start = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    limit.check(&message, &Route::default(), start);
+    limit.check(&message, &Route::default(), start + Duration::minutes(1));
+    limit.check(&message, &Route::default(), start + Duration::minutes(2));
+
+    // The window is up exactly five minutes after it started
+    assert_eq!(
+        limit.check(&message, &Route::default(), start + Duration::seconds(299)),
+        Limit::Suppress
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), start + Duration::minutes(5)),
+        Limit::Post { suppressed: 3 }
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), start + Duration::minutes(6)),
+        Limit::Suppress
+    );
+}
+
+#[test]
+fn each_type_has_its_own_limit() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let now = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        limit.check(&fixture("ec2.json"), &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    assert_eq!(
+        limit.check(&fixture("s3.json"), &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    assert_eq!(
+        limit.check(&fixture("ec2.json"), &Route::default(), now),
+        Limit::Suppress
+    );
+}
+
+#[test]
+fn expired_windows_say_what_they_held_back() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let start = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+    let to = Route {
+        webhook: String::from("https://hooks.slack.com/services/T000/B000/XXXX"),
+        channel: Some(String::from("#guardduty")),
+    };
+
+    limit.check(&fixture("ec2.json"), &to, start);
+    limit.check(&fixture("ec2.json"), &to, start);
+    limit.check(&fixture("ec2.json"), &to, start);
+    limit.check(&fixture("s3.json"), &to, start + Duration::minutes(1));
+
+    assert_eq!(limit.expire(start + Duration::minutes(4)), vec![]);
+    // The S3 window held nothing back, so there's nothing to say about it
+    let held_back = HeldBack {
+        tipe: fixture("ec2.json").detail.tipe,
+        count: 2,
+        to: to.clone(),
+    };
+    assert_eq!(limit.expire(start + Duration::minutes(6)), vec![held_back]);
+
+    // Said once, the next finding starts afresh rather than carrying the count
+    assert_eq!(limit.expire(start + Duration::minutes(7)), vec![]);
+    assert_eq!(
+        limit.check(&fixture("ec2.json"), &to, start + Duration::minutes(7)),
+        Limit::Post { suppressed: 0 }
+    );
+}
+
+#[test]
+fn unposted_findings_dont_use_up_the_window() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let message = fixture("ec2.json");
+    let now = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    limit.unpost(&message);
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Post { suppressed: 0 }
+    );
+    assert_eq!(
+        limit.check(&message, &Route::default(), now),
+        Limit::Suppress
+    );
+}
+
+#[test]
+fn restored_summary_is_carried_by_the_next_finding() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let message = fixture("ec2.json");
+    let start = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    limit.check(&message, &Route::default(), start);
+    limit.check(&message, &Route::default(), start);
+    let held_back = limit.expire(start + Duration::minutes(5));
+    assert_eq!(held_back.len(), 1);
+
+    // The summary couldn't be posted
+    let later = start + Duration::minutes(6);
+    limit.restore(held_back.into_iter().next().unwrap(), later);
+    assert_eq!(
+        limit.check(&message, &Route::default(), later),
+        Limit::Post { suppressed: 1 }
+    );
+}
+
+#[test]
+fn unposted_finding_hands_back_the_count_it_carried() {
+    let limit = RateLimit::new(1, Duration::minutes(5));
+    let mut message = fixture("ec2.json");
+    let start = Utc.with_ymd_and_hms(2021, 12, 22, 12, 0, 0).unwrap();
+
+    limit.check(&message, &Route::default(), start);
+    limit.check(&message, &Route::default(), start);
+    let later = start + Duration::minutes(5);
+    assert_eq!(
+        limit.check(&message, &Route::default(), later),
+        Limit::Post { suppressed: 1 }
+    );
+
+    message.suppressed = 1;
+    limit.unpost(&message);
+    let held_back = limit.expire(later + Duration::minutes(5));
+    assert_eq!(held_back[0].count, 1);
+}
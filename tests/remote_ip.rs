@@ -0,0 +1,126 @@
+mod common;
+
+use common::fixture_json;
+use guarddutyslack::{Config, Message};
+use serde_json::{json, Value};
+
+/// The ec2.json finding with its action swapped for `action`
+fn with_action(action: Value) -> Message {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["service"]["action"] = action;
+    serde_json::from_value(json).unwrap()
+}
+
+fn port_probe(remote_ip_details: Value) -> Message {
+    with_action(json!({
+        "actionType": "PORT_PROBE",
+        "portProbeAction": {
+            "blocked": false,
+            "portProbeDetails": [
+                {
+                    "localPortDetails": { "port": 22, "portName": "SSH" },
+                    "remoteIpDetails": remote_ip_details,
+                },
+            ],
+        },
+    }))
+}
+
+fn field(message: &Message, config: &Config, title: &str) -> Option<String> {
+    let json = serde_json::to_value(message.build_payload(config).unwrap()).unwrap();
+    json["attachments"][0]["fields"]
+        .as_array()?
+        .iter()
+        .find(|f| f["title"] == title)?["value"]
+        .as_str()
+        .map(String::from)
+}
+
+#[test]
+fn network_connection_remote_ip() {
+    let message = with_action(json!({
+        "actionType": "NETWORK_CONNECTION",
+        "networkConnectionAction": {
+            "connectionDirection": "INBOUND",
+            "remoteIpDetails": {
+                "ipAddressV4": "198.51.100.7",
+                "country": { "countryName": "Netherlands" },
+                "city": { "cityName": "Amsterdam" },
+            },
+        },
+    }));
+
+    assert_eq!(message.remote_ip(), Some("198.51.100.7"));
+    assert_eq!(
+        message.remote_location().as_deref(),
+        Some("Amsterdam, Netherlands")
+    );
+}
+
+#[test]
+fn port_probe_remote_ip() {
+    let message = port_probe(json!({
+        "ipAddressV4": "203.0.113.9",
+        "country": { "countryName": "Brazil" },
+    }));
+
+    assert_eq!(message.remote_ip(), Some("203.0.113.9"));
+    assert_eq!(message.remote_location().as_deref(), Some("Brazil"));
+}
+
+#[test]
+fn missing_geo_data_is_no_location() {
+    let message = port_probe(json!({ "ipAddressV4": "203.0.113.9", "city": { "cityName": "" } }));
+    assert_eq!(message.remote_ip(), Some("203.0.113.9"));
+    assert_eq!(message.remote_location(), None);
+
+    let mut config = Config::default();
+    config.show_location = true;
+    assert_eq!(field(&message, &config, "Location"), None);
+    assert_eq!(
+        field(&message, &config, "Remote IP").as_deref(),
+        Some("203.0.113.9")
+    );
+}
+
+#[test]
+fn no_remote_side_is_no_remote_ip() {
+    let message = with_action(json!({
+        "actionType": "DNS_REQUEST",
+        "dnsRequestAction": { "domain": "example.com" },
+    }));
+    assert_eq!(message.remote_ip(), None);
+
+    let message = port_probe(json!("not an object"));
+    assert_eq!(message.remote_ip(), None);
+    assert_eq!(message.remote_location(), None);
+}
+
+#[test]
+fn location_replaces_country_with_show_location() {
+    let message = with_action(json!({
+        "actionType": "NETWORK_CONNECTION",
+        "networkConnectionAction": {
+            "remoteIpDetails": {
+                "ipAddressV4": "198.51.100.7",
+                "country": { "countryName": "Netherlands" },
+                "city": { "cityName": "Amsterdam" },
+            },
+        },
+    }));
+
+    let config = Config::default();
+    assert_eq!(
+        field(&message, &config, "Country").as_deref(),
+        Some("Netherlands")
+    );
+    assert_eq!(field(&message, &config, "Location"), None);
+
+    let mut config = Config::default();
+    config.show_location = true;
+    assert_eq!(field(&message, &config, "Country"), None);
+    assert_eq!(
+        field(&message, &config, "Location").as_deref(),
+        Some("Amsterdam, Netherlands")
+    );
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{fixture, fixture_json};
+use guarddutyslack::{Config, Message, NetworkInterface};
+use serde_json::{json, Value};
+
+#[test]
+fn ec2_resource_from_an_instance_finding() {
+    let instance = fixture("ec2.json")
+        .ec2_resource()
+        .expect("ec2.json is an Instance finding");
+
+    assert_eq!(instance.instance_id, "i-99999999");
+    assert_eq!(instance.instance_type.as_deref(), Some("c3.large"));
+    assert_eq!(instance.tags.len(), 9);
+    assert_eq!(instance.tags[0].key, "GeneratedFindingInstaceTag1");
+    assert_eq!(
+        instance.network_interfaces,
+        vec![NetworkInterface {
+            network_interface_id: Some(String::from("eni-bfcffe88")),
+            private_ip_address: Some(String::from("10.0.0.1")),
+            public_ip: Some(String::from("198.51.100.0")),
+            subnet_id: Some(String::from("GeneratedFindingSubnetId")),
+            vpc_id: Some(String::from("GeneratedFindingVPCId")),
+        }]
+    );
+}
+
+#[test]
+fn ec2_resource_is_none_for_other_resources() {
+    assert_eq!(fixture("s3.json").ec2_resource(), None);
+    assert_eq!(fixture("iamuser.json").ec2_resource(), None);
+}
+
+#[test]
+fn ec2_resource_is_none_when_the_shape_is_unexpected() {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["resource"]["instanceDetails"]
+        .as_object_mut()
+        .unwrap()
+        .remove("instanceId");
+    let message: Message = serde_json::from_value(json).unwrap();
+
+    assert_eq!(message.ec2_resource(), None);
+}
+
+fn with_resources(resources: Value) -> Message {
+    let mut json = fixture_json("ec2.json");
+    json["resources"] = resources;
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn resource_arns_are_deduped() {
+    let message = with_resources(json!([
+        "arn:aws:ec2:eu-west-2:999999999999:instance/i-99999999",
+        "arn:aws:iam::999999999999:role/web",
+        "arn:aws:ec2:eu-west-2:999999999999:instance/i-99999999",
+    ]));
+
+    assert_eq!(
+        message.resource_arns(),
+        vec![
+            "arn:aws:ec2:eu-west-2:999999999999:instance/i-99999999",
+            "arn:aws:iam::999999999999:role/web",
+        ]
+    );
+
+    let json = serde_json::to_value(message.build_payload(&Config::default()).unwrap()).unwrap();
+    let field = json["attachments"][0]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["title"] == "Resource ARNs")
+        .unwrap();
+    assert_eq!(
+        field["value"],
+        "arn:aws:ec2:eu-west-2:999999999999:instance/i-99999999\narn:aws:iam::999999999999:role/web"
+    );
+}
+
+#[test]
+fn no_resource_arns_is_no_field() {
+    for resources in [
+        json!([]),
+        json!(null),
+        json!("arn:aws:s3:::bucket"),
+        json!([42]),
+    ] {
+        let message = with_resources(resources);
+        assert!(message.resource_arns().is_empty());
+
+        let json =
+            serde_json::to_value(message.build_payload(&Config::default()).unwrap()).unwrap();
+        assert!(!json["attachments"][0]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|f| f["title"] == "Resource ARNs"));
+    }
+}
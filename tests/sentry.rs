@@ -0,0 +1,45 @@
+use guarddutyslack::sentry_event;
+use guarddutyslack::Sentry;
+
+#[test]
+fn store_url_from_dsn() {
+    let sentry = Sentry::from_dsn("https://abc123@o42.ingest.sentry.io/4505").unwrap();
+    assert_eq!(
+        sentry.store_url(),
+        "https://o42.ingest.sentry.io/api/4505/store/"
+    );
+
+    let sentry = Sentry::from_dsn("http://abc123@sentry.internal:9000/7").unwrap();
+    assert_eq!(
+        sentry.store_url(),
+        "http://sentry.internal:9000/api/7/store/"
+    );
+}
+
+#[test]
+fn bad_dsn_is_rejected() {
+    assert!(Sentry::from_dsn("not a dsn").is_none());
+    assert!(Sentry::from_dsn("https://o42.ingest.sentry.io/4505").is_none());
+    assert!(Sentry::from_dsn("https://abc123@o42.ingest.sentry.io/").is_none());
+}
+
+#[test]
+fn event_carries_the_finding() {
+    let event = sentry_event(
+        "Failed to deserialize finding abc",
+        Some("abc"),
+        Some("{\"detail\": \"not a finding\"}"),
+    );
+
+    assert_eq!(event["event_id"].as_str().map(str::len), Some(32));
+    assert_eq!(event["level"], "error");
+    assert_eq!(
+        event["message"]["formatted"],
+        "Failed to deserialize finding abc"
+    );
+    assert_eq!(event["tags"]["finding_id"], "abc");
+    assert_eq!(
+        event["extra"]["raw_message"],
+        "{\"detail\": \"not a finding\"}"
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::config_from_env;
+use guarddutyslack::logging;
+use guarddutyslack::settings::{parse, unread};
+
+#[test]
+fn config_json_reads_like_the_environment() {
+    let settings = parse(
+        r#"{
+            "WEBHOOK_URL": "https://hooks.slack.com/services/T000/B000/XXXX",
+            "MIN_SEVERITY": 4.5,
+            "BATCH_MODE": true,
+            "SUPPRESS_TYPES": ["Recon:EC2/PortProbeUnprotectedPort", "Recon:EC2/Portscan"],
+            "ACCOUNT_NAME_MAP": {"123456789012": "prod-payments"},
+            "MENTION_LOW": null
+        }"#,
+    )
+    .unwrap();
+
+    let get = |key: &str| settings.get(key).map(String::as_str);
+    assert_eq!(
+        get("WEBHOOK_URL"),
+        Some("https://hooks.slack.com/services/T000/B000/XXXX")
+    );
+    assert_eq!(get("MIN_SEVERITY"), Some("4.5"));
+    assert_eq!(get("BATCH_MODE"), Some("true"));
+    assert_eq!(
+        get("SUPPRESS_TYPES"),
+        Some("Recon:EC2/PortProbeUnprotectedPort,Recon:EC2/Portscan")
+    );
+    assert_eq!(
+        get("ACCOUNT_NAME_MAP"),
+        Some(r#"{"123456789012":"prod-payments"}"#)
+    );
+    assert_eq!(get("MENTION_LOW"), None);
+}
+
+#[test]
+fn config_json_must_be_an_object() {
+    assert!(parse("not json").unwrap_err().starts_with("not valid JSON"));
+    assert_eq!(
+        parse(r#"["WEBHOOK_URL"]"#).unwrap_err(),
+        "should be a JSON object"
+    );
+}
+
+#[test]
+fn misspelled_keys_are_reported() {
+    let settings = parse(
+        r#"{
+            "WEBHOOK_URL": "https://hooks.slack.com/services/T000/B000/XXXX",
+            "MIN_SEVERTY": 4.5,
+            "BATCH_MODE": true,
+            "DRY_RUN": true
+        }"#,
+    )
+    .unwrap();
+
+    config_from_env(&[(
+        "WEBHOOK_URL",
+        "https://hooks.slack.com/services/T000/B000/XXXX",
+    )])
+    .unwrap();
+    assert_eq!(unread(&settings), vec!["MIN_SEVERTY"]);
+}
+
+#[test]
+fn log_format_is_read_like_any_other_setting() {
+    logging::init();
+
+    let settings = parse(r#"{"LOG_FORMAT": "json"}"#).unwrap();
+    assert_eq!(unread(&settings), Vec::<&str>::new());
+}
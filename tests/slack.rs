@@ -0,0 +1,300 @@
+mod common;
+
+use common::{config_from_env, fixture_json, mock_slack, sns_record};
+use guarddutyslack::{handle, Config, Destination, RateLimit, SlackFormat};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// The default config, posting to `webhook`
+fn config(webhook: &str) -> Config {
+    let mut config = Config::default();
+    config.webhook = webhook.to_string();
+    config
+}
+
+#[tokio::test]
+async fn finding_is_posted_to_the_webhook() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+
+    let response = handle(fixture_json("ec2.json"), &config(&slack.url))
+        .await
+        .unwrap();
+    assert_eq!(response["sent"], 1);
+
+    let requests = slack.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let body: Value = serde_json::from_str(&requests[0]).unwrap();
+    assert_eq!(
+        body["attachments"][0]["title"],
+        "(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(body["link_names"], json!(1));
+}
+
+#[tokio::test]
+async fn server_errors_are_retried() {
+    let slack = mock_slack(&[503, 200], Duration::from_millis(0));
+
+    let response = handle(fixture_json("ec2.json"), &config(&slack.url))
+        .await
+        .unwrap();
+    assert_eq!(response["sent"], 1);
+    assert_eq!(slack.answered.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn rejected_post_fails_without_a_retry() {
+    let slack = mock_slack(&[400], Duration::from_millis(0));
+
+    let error = handle(fixture_json("ec2.json"), &config(&slack.url))
+        .await
+        .expect_err("Slack said no")
+        .to_string();
+    assert!(error.contains("HTTP 400"), "{}", error);
+    assert_eq!(slack.answered.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn errors_name_the_destination() {
+    let teams = mock_slack(&[400], Duration::from_millis(0));
+    let mut config = config(&teams.url);
+    config.destination = Destination::Teams;
+
+    let error = handle(fixture_json("ec2.json"), &config)
+        .await
+        .expect_err("Teams said no")
+        .to_string();
+    assert!(
+        error.contains("to Teams: Teams responded with HTTP 400"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn rejected_post_is_retried_with_retry_on_4xx() {
+    let slack = mock_slack(&[400, 200], Duration::from_millis(0));
+    let mut config = config(&slack.url);
+    config.retry.retry_on_4xx = true;
+
+    let response = handle(fixture_json("ec2.json"), &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+    assert_eq!(slack.answered.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn every_record_is_posted() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let event = json!({
+        "Records": [
+            sns_record(Some(fixture_json("ec2.json").to_string())),
+            sns_record(Some(fixture_json("s3.json").to_string())),
+        ]
+    });
+
+    let response = handle(event, &config(&slack.url)).await.unwrap();
+    assert_eq!(response["sent"], 2);
+    assert_eq!(slack.requests.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn plain_message_is_sent_when_the_attachment_cant_be_built() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = config(&slack.url);
+    // slack-hook3 won't take a title link that isn't a URL
+    config.runbooks.insert(
+        String::from("UnauthorizedAccess"),
+        String::from("see the wiki"),
+    );
+
+    let response = handle(fixture_json("ec2.json"), &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+
+    let requests = slack.requests.lock().unwrap();
+    let body: Value = serde_json::from_str(&requests[0]).unwrap();
+    assert_eq!(
+        body["text"],
+        "GuardDuty finding 96bef0f2aff363c0dd1466bb22a7e32f of type UnauthorizedAccess:EC2/MetadataDNSRebind, severity 8 — <https://eu-west-2.console.aws.amazon.com/guardduty/home?region=eu-west-2#/findings?fId=96bef0f2aff363c0dd1466bb22a7e32f|see console>"
+    );
+    assert!(body.get("attachments").is_none());
+}
+
+/// ec2.json from account 999999999999 and s3.json from 111111111111
+fn two_accounts() -> Value {
+    let mut s3 = fixture_json("s3.json");
+    s3["detail"]["accountId"] = json!("111111111111");
+    json!({
+        "Records": [
+            sns_record(Some(fixture_json("ec2.json").to_string())),
+            sns_record(Some(s3.to_string())),
+        ]
+    })
+}
+
+#[tokio::test]
+async fn mapped_account_posts_to_its_own_webhook() {
+    let default = mock_slack(&[200], Duration::from_millis(0));
+    let customer = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = config(&default.url);
+    config
+        .account_webhooks
+        .insert(String::from("999999999999"), customer.url.clone());
+
+    let event = two_accounts();
+    let response = handle(event, &config).await.unwrap();
+    assert_eq!(response["sent"], 2);
+
+    let posted_to = |slack: &common::MockSlack| -> Vec<Value> {
+        let requests = slack.requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|r| serde_json::from_str::<Value>(r).unwrap()["attachments"][0]["title"].clone())
+            .collect()
+    };
+    assert_eq!(
+        posted_to(&customer),
+        vec![json!("(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind")]
+    );
+    assert_eq!(
+        posted_to(&default),
+        vec![json!("Policy:S3/BucketBlockPublicAccessDisabled")]
+    );
+}
+
+#[tokio::test]
+async fn batches_are_split_by_account_webhook() {
+    let default = mock_slack(&[200], Duration::from_millis(0));
+    let customer = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = config(&default.url);
+    config.batch_mode = true;
+    config
+        .account_webhooks
+        .insert(String::from("999999999999"), customer.url.clone());
+
+    let event = two_accounts();
+    let response = handle(event, &config).await.unwrap();
+    assert_eq!(response["sent"], 2);
+    assert_eq!(customer.requests.lock().unwrap().len(), 1);
+    assert_eq!(default.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn batches_are_split_by_band_webhook() {
+    let default = mock_slack(&[200], Duration::from_millis(0));
+    let high = mock_slack(&[200], Duration::from_millis(0));
+    let (default_url, high_url) = (default.url.clone(), high.url.clone());
+    // Generic, as Slack webhooks have to be on hooks.slack.com
+    let config = tokio::task::spawn_blocking(move || {
+        config_from_env(&[
+            ("DESTINATION", "generic"),
+            ("BATCH_MODE", "true"),
+            ("WEBHOOK_URL", &default_url),
+            ("WEBHOOK_URL_HIGH", &high_url),
+        ])
+    })
+    .await
+    .unwrap()
+    .unwrap();
+
+    let mut low = fixture_json("s3.json");
+    low["detail"]["severity"] = json!(2.0);
+    let event = json!({
+        "Records": [
+            sns_record(Some(fixture_json("ec2.json").to_string())),
+            sns_record(Some(low.to_string())),
+        ]
+    });
+    let response = handle(event, &config).await.unwrap();
+    assert_eq!(response["sent"], 2);
+
+    let requests = high.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].contains("96bef0f2aff363c0dd1466bb22a7e32f"));
+    let requests = default.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].contains("96bef0f2aff363c0dd1466bb22a7e32f"));
+}
+
+#[tokio::test]
+async fn block_kit_batches_are_split_under_the_block_limit() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = config(&slack.url);
+    config.batch_mode = true;
+    config.slack_format = SlackFormat::Blocks;
+
+    let records: Vec<Value> = (0..20)
+        .map(|i| {
+            let mut finding = fixture_json("ec2.json");
+            finding["detail"]["id"] = json!(format!("finding-{:02}", i));
+            sns_record(Some(finding.to_string()))
+        })
+        .collect();
+    let response = handle(json!({ "Records": records }), &config)
+        .await
+        .unwrap();
+    assert_eq!(response["sent"], 20);
+
+    let requests = slack.requests.lock().unwrap();
+    assert!(requests.len() > 1, "{} posts", requests.len());
+    for request in requests.iter() {
+        let body: Value = serde_json::from_str(request).unwrap();
+        assert!(body["blocks"].as_array().unwrap().len() <= 50);
+        assert!(!request.contains("more findings"), "{}", request);
+    }
+    for i in 0..20 {
+        let id = format!("finding-{:02}", i);
+        assert!(
+            requests.iter().any(|r| r.contains(&id)),
+            "{} wasn't posted",
+            id
+        );
+    }
+}
+
+/// `count` copies of ec2.json, each with an id of its own
+fn burst(count: usize) -> Value {
+    let records: Vec<Value> = (0..count)
+        .map(|i| {
+            let mut finding = fixture_json("ec2.json");
+            finding["detail"]["id"] = json!(format!("burst-{:02}", i));
+            sns_record(Some(finding.to_string()))
+        })
+        .collect();
+    json!({ "Records": records })
+}
+
+#[tokio::test]
+async fn held_back_findings_are_summarised_when_the_burst_stops() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = config(&slack.url);
+    config.rate_limit = Some(RateLimit::new(1, chrono::Duration::seconds(1)));
+
+    let response = handle(burst(3), &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+    assert_eq!(response["skipped"], 2);
+    assert_eq!(slack.requests.lock().unwrap().len(), 1);
+
+    // Nothing more of the type comes in, the next invocation after the window says what it held
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    handle(json!({ "warmup": true }), &config).await.unwrap();
+
+    let requests = slack.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let body: Value = serde_json::from_str(&requests[1]).unwrap();
+    assert_eq!(
+        body["text"],
+        "2 more UnauthorizedAccess:EC2/MetadataDNSRebind findings were held back, RATE_LIMIT_COUNT was reached"
+    );
+}
+
+#[tokio::test]
+async fn failed_post_doesnt_use_up_the_rate_limit() {
+    let slack = mock_slack(&[400, 200], Duration::from_millis(0));
+    let mut config = config(&slack.url);
+    config.rate_limit = Some(RateLimit::new(1, chrono::Duration::minutes(5)));
+
+    assert!(handle(burst(1), &config).await.is_err());
+    let response = handle(burst(1), &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::fixture;
+use guarddutyslack::Config;
+
+#[test]
+fn teams_message_card() {
+    let card = fixture("ec2.json").build_teams_card(&Config::default());
+
+    assert_eq!(card["@type"], "MessageCard");
+    assert_eq!(
+        card["summary"],
+        "GuardDuty:UnauthorizedAccess:EC2/MetadataDNSRebind"
+    );
+    assert_eq!(card["themeColor"], "DB6B30");
+
+    let section = &card["sections"][0];
+    assert_eq!(
+        section["activityTitle"],
+        "[(SAMPLE) UnauthorizedAccess:EC2/MetadataDNSRebind](https://docs.aws.amazon.com/guardduty/latest/ug/guardduty_finding-types-ec2.html#unauthorizedaccess-ec2-metadatadnsrebind)"
+    );
+    assert_eq!(
+        section["activitySubtitle"],
+        "New finding in eu-west-2 from account 999999999999"
+    );
+
+    let fact = |name: &str| {
+        section["facts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["name"] == name)
+            .map(|f| f["value"].clone())
+    };
+    assert_eq!(fact("Severity").unwrap(), "8 (High)");
+    assert_eq!(fact("Level").unwrap(), "High");
+    assert_eq!(fact("Count").unwrap(), "1");
+    assert_eq!(fact("First seen").unwrap(), "Wed Dec 22 11:27:25 UTC");
+    assert_eq!(fact("Last seen").unwrap(), "Wed Dec 22 11:27:25 UTC");
+    assert_eq!(fact("Instance").unwrap(), "i-99999999");
+
+    let action = &section["potentialAction"][0];
+    assert_eq!(action["@type"], "OpenUri");
+    assert!(action["targets"][0]["uri"]
+        .as_str()
+        .unwrap()
+        .starts_with("https://eu-west-2.console.aws.amazon.com/guardduty/"));
+}
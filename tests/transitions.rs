@@ -0,0 +1,98 @@
+mod common;
+
+use chrono::Duration as ChronoDuration;
+use common::{fixture, fixture_json, mock_slack};
+use guarddutyslack::{handle, Config, RateLimit, Transitions};
+use serde_json::Value;
+use std::time::Duration;
+
+/// The ec2.json finding updated at `updated_at` with `severity`, as GuardDuty sends it again
+fn ec2_update(updated_at: &str, severity: f32) -> Value {
+    let mut json = fixture_json("ec2.json");
+    json["detail"]["updatedAt"] = updated_at.into();
+    json["detail"]["severity"] = severity.into();
+    json
+}
+
+#[tokio::test]
+async fn new_finding_is_a_transition() {
+    let transitions = Transitions::in_memory();
+    assert!(
+        transitions
+            .is_transition(&fixture("ec2.json"), "High")
+            .await
+    );
+}
+
+#[tokio::test]
+async fn same_band_is_not_a_transition() {
+    let transitions = Transitions::in_memory();
+    let message = fixture("ec2.json");
+
+    assert!(transitions.is_transition(&message, "High").await);
+    assert!(!transitions.is_transition(&message, "High").await);
+}
+
+#[tokio::test]
+async fn escalation_is_a_transition() {
+    let transitions = Transitions::in_memory();
+    let message = fixture("ec2.json");
+
+    assert!(transitions.is_transition(&message, "High").await);
+    assert!(transitions.is_transition(&message, "Critical").await);
+    assert!(!transitions.is_transition(&message, "Critical").await);
+}
+
+#[tokio::test]
+async fn forgotten_finding_is_a_transition_again() {
+    let transitions = Transitions::in_memory();
+    let message = fixture("ec2.json");
+
+    transitions.is_transition(&message, "High").await;
+    transitions.forget(&message).await;
+    assert!(transitions.is_transition(&message, "High").await);
+}
+
+#[tokio::test]
+async fn only_band_changes_are_posted() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = Config::default();
+    config.webhook = slack.url.clone();
+    config.transitions = Some(Transitions::in_memory());
+
+    let first = ec2_update("2021-12-22T11:27:25.670Z", 8.0);
+    let response = handle(first, &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+
+    // Updated, but still high
+    let same_band = ec2_update("2021-12-22T12:00:00Z", 7.5);
+    let response = handle(same_band, &config).await.unwrap();
+    assert_eq!(response["skipped"], 1);
+    assert_eq!(response["findings"][0]["reason"], "no_transition");
+
+    let escalated = ec2_update("2021-12-22T13:00:00Z", 9.5);
+    let response = handle(escalated, &config).await.unwrap();
+    assert_eq!(response["sent"], 1);
+    assert_eq!(slack.requests.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn rate_limited_band_change_is_not_remembered() {
+    let slack = mock_slack(&[200], Duration::from_millis(0));
+    let mut config = Config::default();
+    config.webhook = slack.url.clone();
+    config.transitions = Some(Transitions::in_memory());
+    config.rate_limit = Some(RateLimit::new(1, ChronoDuration::minutes(5)));
+
+    let first = ec2_update("2021-12-22T11:27:25.670Z", 8.0);
+    assert_eq!(handle(first, &config).await.unwrap()["sent"], 1);
+
+    let escalated = ec2_update("2021-12-22T13:00:00Z", 9.5);
+    let response = handle(escalated.clone(), &config).await.unwrap();
+    assert_eq!(response["findings"][0]["reason"], "rate_limited");
+
+    // Still waiting to be posted as critical once the rate limit allows
+    let message = serde_json::from_value(escalated).unwrap();
+    let transitions = config.transitions.as_ref().unwrap();
+    assert!(transitions.is_transition(&message, "Critical").await);
+}
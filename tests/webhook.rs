@@ -0,0 +1,46 @@
+use guarddutyslack::{check_webhook, Destination};
+
+#[test]
+fn slack_webhooks_are_accepted() {
+    let webhook = "https://hooks.slack.com/services/T000/B000/XXXX";
+    assert_eq!(check_webhook(webhook, Destination::Slack), Ok(()));
+}
+
+#[test]
+fn typos_and_placeholders_are_rejected() {
+    for webhook in &[
+        "",
+        "changeme",
+        "http://hooks.slack.com/services/T000/B000/XXXX",
+        "https://hooks.slak.com/services/T000/B000/XXXX",
+    ] {
+        assert!(
+            check_webhook(webhook, Destination::Slack).is_err(),
+            "{}",
+            webhook
+        );
+    }
+}
+
+#[test]
+fn the_reason_leaves_out_the_secret() {
+    let reason = check_webhook(
+        "http://hooks.slack.com/services/T000/B000/XXXX",
+        Destination::Slack,
+    )
+    .unwrap_err();
+    assert!(!reason.contains("XXXX"), "{}", reason);
+}
+
+#[test]
+fn teams_webhooks_can_be_on_any_https_host() {
+    let webhook = "https://example.webhook.office.com/webhookb2/abc";
+    assert_eq!(check_webhook(webhook, Destination::Teams), Ok(()));
+    assert!(check_webhook("http://example.webhook.office.com/x", Destination::Teams).is_err());
+}
+
+#[test]
+fn generic_webhooks_can_be_plain_http() {
+    let webhook = "http://incidents.internal:8080/guardduty";
+    assert_eq!(check_webhook(webhook, Destination::Generic), Ok(()));
+}